@@ -6,15 +6,24 @@
 
 // ----------------------------------           IMPORTS           ----------------------------------
 use std::{
+    collections::HashMap,
     sync::mpsc,
+    env,
+    fmt,
     fs,
     io,
+    io::Write,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process,
+    cell::OnceCell,
+    sync::OnceLock,
     thread,
-    time::Instant
+    time::{Instant, SystemTime}
 };
 
 use crossterm::{
-    event::{EnableMouseCapture, KeyCode},
+    event::{EnableMouseCapture, KeyCode, KeyModifiers},
     event,
     execute,
     event::Event as CEvent,
@@ -23,7 +32,7 @@ use crossterm::{
 
 use tui::{
     Terminal,
-    widgets::{Block, Borders, BorderType, Cell, List, ListItem, ListState, Paragraph, Row, Table, Tabs},
+    widgets::{Block, Borders, BorderType, Cell, List, ListItem, ListState, Paragraph, Row, Table, Tabs, Wrap},
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
@@ -31,9 +40,15 @@ use tui::{
 };
 
 use chrono::{
+    Datelike,
     DateTime,
     Duration,
-    Utc
+    Local,
+    NaiveDate,
+    NaiveDateTime,
+    TimeZone,
+    Utc,
+    Weekday
 };
 
 use serde::{
@@ -47,15 +62,87 @@ use thiserror::Error;
 
 
 // ----------------------------------          CONSTANTS          ----------------------------------
-const DB_PATH: &str = "./data.json";
+const APP_DIR: &str = "todo-cli"; // Name of the app folders in the XDG base directories
+const DB_FILE_NAME: &str = "data.json";
+const CONFIG_FILE_NAME: &str = "config.json";
+const STATE_FILE_NAME: &str = "state.json"; // Remembers interface settings between sessions
+const LEGACY_DB_PATH: &str = "./data.json"; // Where older versions kept the db
+const DB_PATH_VAR: &str = "TODO_CLI_DB"; // Overrides the db location from the config
+const BACKUP_COUNT: usize = 5; // How many rolling backups are kept next to the db
+const BACKUP_COUNT_VAR: &str = "TODO_CLI_BACKUPS"; // Overrides BACKUP_COUNT
+const HISTORY_LIMIT: usize = 100; // How many changes can be undone
+const TRASH_DAYS: i64 = 30; // Days after which trashed TODOs are purged, 0 keeps them forever
+const USAGE: &str = "usage: todo-cli [--db <path>] [<command>]
+
+Without a command the interactive interface is started.
+
+commands:
+    add <name> [--category <category>] [--tags <tags>] [--text <text>] [--priority <priority>] [--due <due>]
+        [--parent <id>]    the new TODO is a subtask of the given one
+        [--blocked-by <ids>]
+                           the new TODO waits for the given ones, like \"3,12\"
+        [--repeat <rule>]  daily, weekdays, \"weekly on mon, fri\", \"monthly on 15\" or
+                           \"every 3 days after completion\"; marking it done adds the next one
+                           #tag words in the name, category or text are taken as tags as well
+    list [<filter>...] [--format table|json|tsv]
+    show <id> [--format table|json|tsv]
+                           TODOs in the trash are listed by trash instead
+    rm <id>                move a TODO to the trash
+    done <id> [--subtasks] also mark all subtasks as done
+    trash [<filter>...] [--format table|json|tsv]
+    restore <id>           move a TODO out of the trash
+    purge <id>             delete a TODO from the trash for good
+    repair [--renumber]    give TODOs that share an id a new one, or renumber all of them
+    categories             list the categories with the number of TODOs in each
+    migrate                move the TODOs of an older version from ./data.json, other commands
+                           that change TODOs refuse to run until then
+    rename-category <category> <new category>
+                           move all TODOs of a category to another one, merging them if it exists
+
+json and tsv output use the field names of the database file.
+
+filters, all terms have to match and the same query works in the search of the interface:
+    <word>                 fuzzy search in name, category and text, \"quote\" to keep spaces
+    name:<text>            name contains the text, also text:; name:~<text> for a fuzzy match
+    category:<category>    category is exactly this, ignoring case
+    #<tag>                 has the tag, also tag:<tag>; tag:~<tag> for a fuzzy match
+    status:<status>        open, in-progress, done or cancelled
+    priority:<priority>    none, low, medium, high or urgent, priority:>low for higher ones
+    id:<id>
+    is:ready               open and all TODOs it is blocked by are done; is:blocked for the others
+    created:<date>         also due:, completed: and updated:; a date is YYYY-MM-DD, YYYY-MM,
+                           today, yesterday, tomorrow, this-week, last-week, next-week,
+                           this-month, last-month, next-month or none; < and > compare
+    -<term>                leave out TODOs matching the term
+    use -- before a left out <word>, like -- -milk
+
+exit codes:
+    0  success
+    1  other errors
+    2  invalid command line
+    3  TODO or category not found
+    4  database file is corrupt
+    5  database file can not be read or written";
+const BOOLEAN_FLAGS: [&str; 2] = ["--renumber", "--subtasks"]; // Command line options that take no value
+const TODO_FIELDS: [&str; 18] = [ // Field names of a serialized to_do, in output order
+    "id", "uuid", "name", "category", "tags", "text", "created_at", "status", "completed_at", "priority",
+    "due", "updated_at", "deleted_at", "position", "parent", "blocked_by", "checklist", "recurrence"
+];
+
 const ACTIVE_COLOR: Color = Color::White;
 const INACTIVE_COLOR: Color = Color::DarkGray;
 const BG_HIGHLIGHT_COLOR: Color = Color::Gray;
 const FOCUS_COLOR: Color = Color::LightMagenta;
+const TAG_COLOR: Color = Color::Cyan; // For tags without a color in the theme
+const BLOCKED_COLOR: Color = Color::LightRed;
 
 
 const DEFAULT_BORDER: BorderType = BorderType::Plain;
 const FOCUS_BORDER: BorderType = BorderType::Double;
+
+
+static DB_PATH: OnceLock<PathBuf> = OnceLock::new(); // Resolved once at startup, see db_path()
+static CONFIG: OnceLock<Config> = OnceLock::new();
 // ----------------------------------       END OF CONSTANTS      ----------------------------------
 
 
@@ -63,384 +150,4259 @@ const FOCUS_BORDER: BorderType = BorderType::Double;
 #[derive(Serialize, Deserialize, Clone)]
 struct Todo {
     id: usize,
+    #[serde(default)]
+    uuid: Option<String>, // Identifies the to_do across machines, ids are only unique per db
     name: String,
     category: String,
+    #[serde(default)]
+    tags: Vec<String>, // Lowercase, sorted and without the leading '#'
     text: String,
     created_at: DateTime<Utc>,
+    #[serde(default)]
+    status: TodoStatus,
+    #[serde(default)]
+    completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>, // Set while the to_do is in the trash
+    #[serde(default)]
+    position: usize, // Place in the manual order, ties keep the order of the db
+    #[serde(default)]
+    parent: Option<usize>, // Id of the to_do this one is a subtask of
+    #[serde(default)]
+    blocked_by: Vec<usize>, // Ids of the to_dos that have to be done first
+    #[serde(default)]
+    checklist: Vec<ChecklistItem>,
+    #[serde(default)]
+    recurrence: Option<Recurrence>, // Completing the to_do adds the next occurrence
 }
 
 impl Todo {
     fn default() -> Todo {
-        let temp = Todo {
+        Todo {
             id: 0,
+            uuid: None,
             name: "".to_string(),
             category: "".to_string(),
+            tags: Vec::new(),
             text: "".to_string(),
             created_at: Default::default(),
+            status: TodoStatus::Open,
+            completed_at: None,
+            priority: Priority::None,
+            due: None,
+            updated_at: None,
+            deleted_at: None,
+            position: 0,
+            parent: None,
+            blocked_by: Vec::new(),
+            checklist: Vec::new(),
+            recurrence: None,
+        }
+    }
+
+    fn is_completed(&self) -> bool { // Done and cancelled TODOs count as completed
+        matches!(self.status, TodoStatus::Done | TodoStatus::Cancelled)
+    }
+
+    fn set_status(&mut self, status: TodoStatus) { // Change status and keep completed_at in sync
+        self.status = status;
+        self.completed_at = if self.is_completed() {
+            Some(self.completed_at.unwrap_or_else(Utc::now))
+        } else {
+            None
         };
-        temp
     }
+
+    fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    fn purge_at(&self) -> Option<DateTime<Utc>> { // When the to_do is deleted for good, if it is trashed
+        let days = trash_days();
+        match self.deleted_at {
+            Some(deleted_at) if days > 0 => Some(deleted_at + Duration::days(days)),
+            _ => None,
+        }
+    }
+
+    fn tags_not_in(&self, fields: &[&str]) -> Vec<String> { // Tags that no #tag word of the fields gives
+        let written: Vec<String> = fields.iter().flat_map(|field| tags_in(field)).collect();
+        self.tags.iter().filter(|tag| !written.contains(tag)).cloned().collect()
+    }
+
+    fn checklist_percentage(&self) -> Option<usize> { // How much of the checklist is checked, if there is one
+        let checked = self.checklist.iter().filter(|item| item.checked).count();
+        (!self.checklist.is_empty()).then(|| checked * 100 / self.checklist.len())
+    }
+
+    fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        !self.is_completed() && self.due.is_some_and(|due| due < now)
+    }
+
+    fn is_due_today(&self, now: DateTime<Utc>) -> bool {
+        !self.is_completed() && self.due.is_some_and(|due| {
+            due.with_timezone(&Local).date_naive() == now.with_timezone(&Local).date_naive()
+        })
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ChecklistItem { // Holds one line of the checklist of a to_do
+    text: String,
+    checked: bool,
 }
 
 
 struct InputStates { // Holds all the input data
     name: String,
     category: String,
+    tags: String, // Separated by spaces or commas, the '#' is optional
     text: String,
+    priority: Priority,
+    due: String,
+    parent: Option<usize>, // Id of the to_do a new one is added below
+    blocked_by: String, // Ids separated by spaces or commas
+    repeat: String, // A recurrence rule like "weekly on mon"
 }
-// ----------------------------------        END OF STRUCTS       ----------------------------------
 
+impl InputStates {
+    fn default() -> InputStates {
+        InputStates {
+            name: "".to_string(),
+            category: "".to_string(),
+            tags: "".to_string(),
+            text: "".to_string(),
+            priority: Priority::None,
+            due: "".to_string(),
+            parent: None,
+            blocked_by: "".to_string(),
+            repeat: "".to_string(),
+        }
+    }
 
-// ----------------------------------            ENUMS            ----------------------------------
-#[derive(PartialEq, Clone, Copy)]
-enum FocusedInput { // Holds the current focused input
-    Name,
-    Category,
-    Text,
-    None
+    fn from_todo(todo: &Todo) -> InputStates { // Fill the form with an existing to_do
+        InputStates {
+            name: todo.name.clone(),
+            category: todo.category.clone(),
+            tags: format_tags(&todo.tags_not_in(&[&todo.name, &todo.category, &todo.text])),
+            text: todo.text.clone(),
+            priority: todo.priority,
+            due: match todo.due {
+                Some(due) => due.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+                None => "".to_string(),
+            },
+            parent: todo.parent,
+            blocked_by: format_ids(&todo.blocked_by),
+            repeat: todo.recurrence.as_ref().map_or_else(String::new, Recurrence::describe),
+        }
+    }
+
+    fn all_tags(&self) -> Vec<String> { // The tags field and the #tag words of the other fields, which stay as typed
+        let mut tags = parse_tags(&self.tags);
+        tags.extend(
+            [&self.name, &self.category, &self.text]
+                .into_iter()
+                .flat_map(|field| tags_in(field))
+        );
+        tags.sort();
+        tags.dedup();
+        tags
+    }
 }
 
 
-enum Event<I> {
-    Input(I),
-    Tick
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct Store { // Holds everything saved in the db file
+    #[serde(default)]
+    next_id: usize,
+    todos: Vec<Todo>,
 }
 
+impl Store {
+    fn allocate_id(&mut self) -> usize { // Hand out an id that no to_do has used yet
+        let id = self.todos
+            .iter()
+            .map(|todo| todo.id + 1)
+            .fold(self.next_id.max(1), usize::max);
+        self.next_id = id + 1;
+        id
+    }
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("error reading the DB file: {0}")]
-    ReadDBError(#[from] io::Error),
-    #[error("error parsing the DB file: {0}")]
-    ParseDBError(#[from] serde_json::Error),
-}
+    fn live_todo_mut(&mut self, id: usize) -> Result<&mut Todo, Error> { // Trashed to_dos only come back by restoring
+        self.todos.iter_mut().find(|todo| todo.id == id && !todo.is_trashed()).ok_or(Error::TodoNotFound(id))
+    }
 
+    fn duplicate_ids(&self) -> Vec<usize> { // Ids that are shared by more than one to_do
+        let mut seen = std::collections::HashSet::new();
+        let mut duplicates: Vec<usize> = self.todos
+            .iter()
+            .filter(|todo| !seen.insert(todo.id))
+            .map(|todo| todo.id)
+            .collect();
+        duplicates.sort();
+        duplicates.dedup();
+        duplicates
+    }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum MenuItem { // Holds the menu tabs that can be opened
-    Home,
-    TODOs,
-    Add
-}
+    fn repair_ids(&mut self, renumber: bool)
+        -> Vec<(usize, usize, String)> { // Make ids unique, returns (old id, new id, name)
+        if renumber {
+            self.next_id = 1;
+        }
+        let mut seen = std::collections::HashSet::new();
+        let mut changes = Vec::new();
+        let mut new_ids = HashMap::new(); // Subtasks of a shared id stay with the first to_do that has it
 
-impl From<MenuItem> for usize {
-    fn from(input: MenuItem) -> usize {
-        match input {
-            MenuItem::Home => 0,
-            MenuItem::TODOs => 1,
-            MenuItem::Add => 2,
+        for index in 0..self.todos.len() {
+            let old_id = self.todos[index].id;
+            if renumber || !seen.insert(old_id) {
+                let new_id = if renumber {
+                    self.next_id += 1;
+                    self.next_id - 1
+                } else {
+                    self.allocate_id()
+                };
+                seen.insert(new_id);
+                new_ids.entry(old_id).or_insert(new_id);
+                self.todos[index].id = new_id;
+                if new_id != old_id {
+                    changes.push((old_id, new_id, self.todos[index].name.clone()));
+                }
+            }
+            if self.todos[index].uuid.is_none() {
+                self.todos[index].uuid = Some(new_uuid());
+            }
+        }
+
+        if renumber { // Point subtasks and blockers at the new ids, purged to_dos are dropped from both
+            for todo in self.todos.iter_mut() {
+                todo.parent = todo.parent.and_then(|parent| new_ids.get(&parent).copied());
+                todo.blocked_by = todo.blocked_by.iter().filter_map(|blocker| new_ids.get(blocker).copied()).collect();
+            }
         }
+        changes
     }
 }
-// ----------------------------------         END OF ENUMS        ----------------------------------
 
 
-// ----------------------------------      UI BLOCK FUNCTIONS     ----------------------------------
-fn copyright_block<'a>() -> Paragraph<'a> { // Render the fake copyright block
-    Paragraph::new("todo-CLI 2023 --- all rights reserved")
-        .style(Style::default().fg(FOCUS_COLOR))
-        .alignment(Alignment::Center)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(ACTIVE_COLOR))
-                .title("Copyright")
-                .border_type(DEFAULT_BORDER)
-        )
+#[derive(Serialize, Deserialize, Default)]
+struct History { // Holds the changes that can be undone and redone, kept next to the db
+    undo: Vec<Change>,
+    redo: Vec<Change>,
 }
 
+impl History {
+    fn record(&mut self, change: Change) { // Remember a new change, this drops everything undone
+        self.undo.push(change);
+        self.redo.clear();
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.drain(..self.undo.len() - HISTORY_LIMIT);
+        }
+    }
+}
 
-fn render_add<'a>(input_states: &InputStates, focused_input: &FocusedInput) // Render the Add tab
-                  -> (Paragraph<'a>, Paragraph<'a>, Paragraph<'a>, Paragraph<'a>) {
 
-    // Draw help text
-    let help_block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(DEFAULT_BORDER)
-        .style(Style::default().fg(ACTIVE_COLOR))
-        .title("Help");
+struct App { // Holds the state of the interactive interface
+    store: Store, // In-memory copy of the db, written back by save()
+    db_error: Option<serde_json::Error>, // Set while the db file can not be parsed
+    db_unreadable: bool, // Set while the db file can not be read at all
+    quarantine: Option<String>, // Where a corrupt db was copied to
+    legacy_db: Option<PathBuf>, // An old ./data.json that has to be moved before anything is saved
+    active_menu_item: MenuItem,
+    todo_list_state: ListState,
+    backup_list_state: ListState,
+    trash_list_state: ListState,
+    list_options: ListOptions,
+    search_error: Option<String>, // Why the search could not be parsed
+    inputs: InputStates,
+    editing: Option<usize>, // Id of the to_do being edited
+    renaming: Option<String>, // Category being renamed
+    rename_input: String, // New name typed for the renamed category
+    collapsed: Vec<String>, // Categories whose to_dos are folded away while grouping
+    folded: Vec<usize>, // Ids of the to_dos whose subtasks are folded away
+    rows: OnceCell<Vec<ListRow>>, // Lines of the list, emptied by invalidate_rows when they change
+    counts: OnceCell<ListCounts>, // Numbers next to the views, categories and tags, emptied along with rows
+    rows_date: NaiveDate, // Day the lines were last emptied on, views like "due:today" move at midnight
+    checklist_state: ListState, // Selected item in the checklist of the selected to_do
+    checklist_input: String, // Text typed for a checklist item
+    checklist_editing: Option<usize>, // Checklist item being edited, None while adding one
+    focused_input: FocusedInput,
+    status_message: Option<String>,
+    db_stamp: Option<(SystemTime, u64)>, // Modification time and size of the db when last read or written
+}
 
-    let help =
-        Paragraph::new("Use <tab> to switch between fields, <enter> to submit")
-        .block(help_block)
-        .style(Style::default().fg(FOCUS_COLOR));
+impl App {
+    fn new() -> App {
+        let mut todo_list_state = ListState::default();
+        todo_list_state.select(Some(0));
+        let mut backup_list_state = ListState::default();
+        backup_list_state.select(Some(0));
+        let mut trash_list_state = ListState::default();
+        trash_list_state.select(Some(0));
 
-    // Create the blocks
-    let text_block = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(ACTIVE_COLOR))
-        .title("Text")
-        .border_type(
-            if focused_input == &FocusedInput::Text {
-                FOCUS_BORDER
-            } else {
-                DEFAULT_BORDER
-            }
-        )
-        .border_style(Style::default().fg(
-            if focused_input == &FocusedInput::Text {
-                FOCUS_COLOR
-            } else {
-                ACTIVE_COLOR
-            }
-        ));
+        let ui_state = read_ui_state();
+        let mut app = App {
+            store: Store::default(),
+            db_error: None,
+            db_unreadable: false,
+            quarantine: None,
+            legacy_db: None,
+            active_menu_item: MenuItem::Home,
+            todo_list_state,
+            backup_list_state,
+            trash_list_state,
+            list_options: ListOptions {
+                show_completed: true,
+                sort: ui_state.sort,
+                search: String::new(),
+                filter: Vec::new(),
+                view: 0,
+                category: None,
+                group: ui_state.group,
+            },
+            search_error: None,
+            inputs: InputStates::default(),
+            editing: None,
+            renaming: None,
+            rename_input: String::new(),
+            collapsed: Vec::new(),
+            folded: Vec::new(),
+            rows: OnceCell::new(),
+            counts: OnceCell::new(),
+            rows_date: Local::now().date_naive(),
+            checklist_state: ListState::default(),
+            checklist_input: String::new(),
+            checklist_editing: None,
+            focused_input: FocusedInput::None,
+            status_message: None,
+            db_stamp: None,
+        };
+        app.reload();
+        app
+    }
 
-    let name_block = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Gray))
-        .title("Name")
-        .border_type(
-            if focused_input == &FocusedInput::Name {
-                FOCUS_BORDER
-            } else {
-                DEFAULT_BORDER
-            }
-        )
-        .border_style(Style::default().fg(
-            if focused_input == &FocusedInput::Name {
-                FOCUS_COLOR
-            } else {
-                ACTIVE_COLOR
+    fn reload(&mut self) { // Replace the in-memory to_dos with the contents of the db
+        let selected_id = self.selected_index().map(|index| self.store.todos[index].id);
+        self.db_stamp = db_stamp();
+        self.legacy_db = find_legacy_db(); // Moved or filled from the command line meanwhile
+        match read_db(db_path()) {
+            Ok(store) => {
+                self.store = store;
+                self.db_error = None;
+                self.db_unreadable = false;
+                self.quarantine = None;
+                if self.active_menu_item == MenuItem::Recovery { // The file was fixed, show it
+                    self.active_menu_item = MenuItem::TODOs;
+                }
             }
-        ));
-
-    let category_block = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Gray))
-        .title("Category")
-        .border_type(
-            if focused_input == &FocusedInput::Category {
-                FOCUS_BORDER
-            } else {
-                DEFAULT_BORDER
+            Err(Error::ParseDBError(error)) => {
+                self.store = Store::default();
+                self.db_unreadable = false;
+                if self.quarantine.is_none() { // Save the bad file once
+                    self.quarantine = quarantine_db(db_path()).ok();
+                }
+                self.db_error = Some(error);
             }
-        )
-        .border_style(Style::default().fg(
-            if focused_input == &FocusedInput::Category {
-                FOCUS_COLOR
-            } else {
-                ACTIVE_COLOR
+            Err(error) => { // Saving the empty list would overwrite the file
+                self.store = Store::default();
+                self.db_unreadable = true;
+                self.status_message = Some(error.to_string());
             }
-        ));
+        }
+        self.invalidate_rows();
 
-    // Draw the name field
-    let name = Paragraph::new("Name for a TODO: ".to_owned() + &input_states.name)
-        .block(name_block)
-        .style(Style::default().fg(
-            if focused_input == &FocusedInput::Name {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
-            }
+        let amount = self.rows().len(); // Keep the same to_do selected if it still exists
+        let position = selected_id.and_then(|id| self.row_of(id));
+        let selected = self.todo_list_state.selected().unwrap_or(0);
+        self.todo_list_state.select(Some(
+            position.unwrap_or_else(|| selected.min(amount.saturating_sub(1)))
         ));
+    }
 
-    // Draw the category field
-    let category = Paragraph::new("Category for a TODO: ".to_owned() + &input_states.category)
-        .block(category_block)
-        .style(Style::default().fg(
-            if focused_input == &FocusedInput::Category {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
-            }
-        ));
+    fn refresh(&mut self) { // Reload the to_dos if the db was changed by someone else
+        if db_stamp() != self.db_stamp {
+            self.reload();
+        } else if self.rows_date != Local::now().date_naive() {
+            self.invalidate_rows();
+        }
+    }
 
-    // Draw the Text field
-    let text = Paragraph::new("Text for a TODO: ".to_owned() + &input_states.text)
-        .block(text_block)
-        .style(Style::default().fg(
-            if focused_input == &FocusedInput::Text {
-                ACTIVE_COLOR
-            } else {
-                INACTIVE_COLOR
+    fn save(&mut self, change: &Change) -> bool { // Write the to_dos with a change made to them, returns whether that worked
+        if db_stamp() != self.db_stamp { // Changed by someone else since it was read, make the change to their version
+            self.reload();
+            if let Err(error) = change.apply(&mut self.store) {
+                self.status_message = Some(format!("Could not finish {} after the db changed: {}", change.describe(), error));
+                return false;
             }
-        ));
+        }
+        let result = match self.db_error {
+            Some(_) => Err(Error::CorruptDBError),
+            None if self.db_unreadable => Err(Error::UnreadableDBError),
+            None => match &self.legacy_db { // Writing first would leave the old list behind for good
+                Some(legacy_db) => Err(Error::LegacyDBFound(legacy_db.clone())),
+                None => write_db(db_path(), &self.store),
+            },
+        };
+        if let Err(error) = result { // Throw away the changes so memory matches the file
+            self.status_message = Some(error.to_string());
+            self.reload();
+            return false;
+        }
+        self.invalidate_rows();
+        self.db_stamp = db_stamp();
+        true
+    }
 
-    (help, name, category, text)
-}
+    fn rows(&self) -> &[ListRow] { // Lines of the list, with headers while grouping by category
+        self.rows.get_or_init(|| list_rows(&self.store.todos, &self.list_options, &self.collapsed, &self.folded))
+    }
 
+    fn counts(&self) -> &ListCounts {
+        self.counts.get_or_init(|| list_counts(&self.store.todos, &self.list_options))
+    }
 
-fn render_todos<'a>(todo_list_state: &ListState) -> (List<'a>, Table<'a>) { // render TODOs tab
+    fn invalidate_rows(&mut self) { // List the lines again, call this after changing the to_dos or how they are listed
+        self.rows = OnceCell::new();
+        self.counts = OnceCell::new();
+        self.rows_date = Local::now().date_naive();
+    }
 
-    // Create a block for displaying TODOs
-    let todos = Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(ACTIVE_COLOR))
-        .title("TODOs")
-        .border_type(DEFAULT_BORDER);
+    fn row_of(&self, id: usize) -> Option<usize> { // Line of the to_do with the given id, if it is listed
+        self.rows()
+            .iter()
+            .position(|row| matches!(row, ListRow::Todo { index, .. } if self.store.todos[*index].id == id))
+    }
 
-    // Create a list for navigation between TODOs
-    let todo_list = read_db().expect("can fetch todo list");
-    let items: Vec<_> = todo_list
-        .iter()
-        .map(|todo| {
-            ListItem::new(Spans::from(vec![Span::styled(
-                todo.name.clone(),
-                Style::default(),
-            )]))
-        })
-        .collect();
+    fn selected_index(&self) -> Option<usize> { // Index of the selected to_do in the store
+        match self.rows().get(self.todo_list_state.selected()?)? {
+            ListRow::Todo { index, .. } => Some(*index),
+            ListRow::Group { .. } => None,
+        }
+    }
 
-    let selected_todo = match todo_list_state.selected() { // Get the selected to_do
-        None => {
-            Todo::default()
+    fn selected_category(&self) -> Option<String> { // Category of the selected line, or the shown one
+        match self.rows().get(self.todo_list_state.selected()?) {
+            Some(ListRow::Group { category, .. }) => Some(category.clone()),
+            Some(ListRow::Todo { index, .. }) => Some(self.store.todos[*index].category.to_uppercase()),
+            None => self.list_options.category.clone(),
         }
-        Some(selection) => {
-            match todo_list.get(selection) {
-                None => {Todo::default()}
-                Some(todo) => { todo.clone() }
+    }
+
+    fn set_search(&mut self, search: String) { // Filter the list, keeping the selected to_do if it still matches
+        let selected_id = self.selected_index().map(|index| self.store.todos[index].id);
+        match parse_filter(&search, Local::now()) { // Keep the last valid filter while the query is incomplete
+            Ok(filter) => {
+                self.list_options.filter = filter;
+                self.search_error = None;
+            }
+            Err(error) => { // The prompt is too narrow for the message
+                self.search_error = Some(error.to_string());
+                self.status_message = self.search_error.clone();
             }
         }
-    };
+        self.list_options.search = search;
+        self.invalidate_rows();
+        let position = selected_id.and_then(|id| self.row_of(id));
+        self.todo_list_state.select(Some(position.unwrap_or(0)));
+    }
 
-    // Put the list inside the block
-    let list = List::new(items).block(todos).highlight_style(
-        Style::default()
-            .bg(BG_HIGHLIGHT_COLOR)
-            .fg(FOCUS_COLOR)
-            .add_modifier(Modifier::BOLD),
-    );
+    fn set_sort(&mut self, sort: Vec<SortKey>) { // Sort the list, keeping the selected to_do and the choice
+        let selected_id = self.selected_index().map(|index| self.store.todos[index].id);
+        self.list_options.sort = sort;
+        self.invalidate_rows();
+        let position = selected_id.and_then(|id| self.row_of(id));
+        self.todo_list_state.select(Some(position.unwrap_or(0)));
+        self.remember_list_options();
+    }
 
-    // Create a table
-    let todo_detail = Table::new(vec![Row::new(vec![
-        Cell::from(Span::raw(selected_todo.id.to_string())),
-        Cell::from(Span::raw(selected_todo.name)),
-        Cell::from(Span::raw(selected_todo.category)),
-        Cell::from(Span::raw(selected_todo.text)),
-        Cell::from(Span::raw(selected_todo.created_at.to_string())),
-    ])])
-        .header(Row::new(vec![
-            Cell::from(Span::styled(
-                "ID",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Name",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Category",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Text",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Created At",
-                Style::default().add_modifier(Modifier::BOLD),
+    fn toggle_group(&mut self) { // Group the to_dos by category or list them all together
+        let selected_id = self.selected_index().map(|index| self.store.todos[index].id);
+        self.list_options.group = !self.list_options.group;
+        self.invalidate_rows();
+        let position = selected_id.and_then(|id| self.row_of(id));
+        self.todo_list_state.select(Some(position.unwrap_or(0)));
+        self.remember_list_options();
+    }
+
+    fn remember_list_options(&mut self) { // Keep the sort order and the grouping for the next session
+        let state = UiState { sort: self.list_options.sort.clone(), group: self.list_options.group };
+        if let Err(error) = write_ui_state(&state) {
+            self.status_message = Some(format!("Could not remember the sort order: {}", error));
+        }
+    }
+
+    fn cycle_sort(&mut self, secondary: bool) { // Move the primary or the secondary sort key to the next one
+        let primary = self.list_options.sort.first().copied().unwrap_or(SortKey::Manual);
+        let tie_breaker = self.list_options.sort.get(1).copied();
+        let sort = if !secondary {
+            let primary = primary.next();
+            match tie_breaker { // Manual order has no ties and a key does not break its own ties
+                Some(key) if primary != SortKey::Manual && key != primary => vec![primary, key],
+                _ => vec![primary],
+            }
+        } else if primary == SortKey::Manual {
+            return;
+        } else {
+            let mut key = tie_breaker.unwrap_or(SortKey::Manual).next();
+            if key == primary {
+                key = key.next();
+            }
+            match key { // Manual stands for no second key
+                SortKey::Manual => vec![primary],
+                _ => vec![primary, key],
+            }
+        };
+        self.set_sort(sort);
+    }
+
+    fn select_view(&mut self, view: usize) { // Show the to_dos of a saved view, 0 shows all of them
+        if view > config().views.len() {
+            return;
+        }
+        self.list_options.view = view;
+        self.invalidate_rows();
+        self.todo_list_state.select(Some(0));
+        if let Some(Err(error)) = self.list_options.view().map(|view| view.parse(Local::now())) {
+            self.status_message = Some(format!("The view can not be shown, {}", error));
+        }
+    }
+
+    fn select_category(&mut self, forward: bool) { // Show the next or previous category, or all of them
+        let mut choices: Vec<Option<String>> = vec![None];
+        choices.extend(categories(&self.store.todos).into_iter().map(Some));
+        let current = choices.iter().position(|choice| *choice == self.list_options.category).unwrap_or(0);
+        let next = if forward { current + 1 } else { current + choices.len() - 1 };
+        self.list_options.category = choices.swap_remove(next % choices.len());
+        self.invalidate_rows();
+        self.todo_list_state.select(Some(0));
+    }
+
+    fn toggle_collapsed(&mut self) { // Fold or unfold the group of the selected header or the selected subtasks
+        match self.todo_list_state.selected().and_then(|selected| self.rows().get(selected)) {
+            Some(ListRow::Group { category, .. }) => {
+                match self.collapsed.iter().position(|collapsed| collapsed == category) {
+                    Some(position) => { self.collapsed.remove(position); }
+                    None => self.collapsed.push(category.clone()),
+                }
+            }
+            Some(&ListRow::Todo { index, subtasks: (_, all), .. }) if all > 0 => {
+                let id = self.store.todos[index].id;
+                match self.folded.iter().position(|&folded| folded == id) {
+                    Some(position) => { self.folded.remove(position); }
+                    None => self.folded.push(id),
+                }
+            }
+            _ => return,
+        }
+        self.invalidate_rows();
+    }
+
+    fn selected_siblings(&self) -> Option<(Vec<usize>, usize)> { // Indices of the to_dos listed on the level of the selected one, and its place
+        let rows = self.rows();
+        let selected = self.todo_list_state.selected()?;
+        let &ListRow::Todo { index, depth, .. } = rows.get(selected)? else {
+            return None;
+        };
+        let inside = |row: &ListRow| matches!(row, ListRow::Todo { depth: other, .. } if *other >= depth);
+        let start = rows[..selected].iter().rposition(|row| !inside(row)).map_or(0, |position| position + 1);
+        let end = rows[selected..].iter().position(|row| !inside(row)).map_or(rows.len(), |position| selected + position);
+        let siblings: Vec<usize> = rows[start..end]
+            .iter()
+            .filter_map(|row| match row {
+                ListRow::Todo { index, depth: other, .. } if *other == depth => Some(*index),
+                _ => None,
+            })
+            .collect();
+        let place = siblings.iter().position(|&sibling| sibling == index)?;
+        Some((siblings, place))
+    }
+
+    fn set_selected_parent(&mut self, indent: bool) { // Make the selected to_do a subtask of the one above, or of its grandparent
+        let Some((siblings, place)) = self.selected_siblings() else {
+            return;
+        };
+        let todo = &self.store.todos[siblings[place]];
+        let parent = if indent {
+            match place.checked_sub(1) {
+                Some(above) => Some(self.store.todos[siblings[above]].id),
+                None => return,
+            }
+        } else {
+            match todo.parent.and_then(|parent| self.store.todos.iter().find(|other| other.id == parent)) {
+                Some(parent) => parent.parent,
+                None => return,
+            }
+        };
+        let id = todo.id;
+        self.folded.retain(|&folded| Some(folded) != parent); // Keep the moved to_do in sight
+        self.invalidate_rows();
+        if let Ok(change) = set_parent_with_id(&mut self.store, id, parent) {
+            if self.save(&change) {
+                self.record(change);
+            }
+        }
+        if let Some(row) = self.row_of(id) {
+            self.todo_list_state.select(Some(row));
+        }
+    }
+
+    fn complete_selected_with_subtasks(&mut self) { // Mark the selected to_do and everything below it as done
+        if let Some(index) = self.selected_index() {
+            let (id, name) = (self.store.todos[index].id, self.store.todos[index].name.clone());
+            match complete_with_subtasks(&mut self.store, id) {
+                Ok(Change::Batch { changes, .. }) if changes.is_empty() => {
+                    self.status_message = Some(format!("'{}' and its subtasks are already done", name));
+                }
+                Ok(change) if self.save(&change) => {
+                    self.status_message = Some(format!("Marked '{}' and its subtasks as done", name));
+                    self.record(change);
+                }
+                Ok(_) => {}
+                Err(error) => self.status_message = Some(error.to_string()),
+            }
+        }
+    }
+
+    fn rename_selected_category(&mut self) { // Give all to_dos of the category being renamed the typed one
+        let Some(category) = self.renaming.take() else {
+            return;
+        };
+        let new_name = self.rename_input.trim().to_uppercase();
+        let count = self.store.todos.iter().filter(|todo| todo.category.to_uppercase() == category).count();
+        match rename_category(&mut self.store, &category, &new_name) {
+            Ok(change) => {
+                let message = format!(
+                    "Moved {} TODO{} from {} to {}",
+                    count, if count == 1 { "" } else { "s" }, category_label(&category), category_label(&new_name)
+                );
+                if !self.save(&change) {
+                    return;
+                }
+                self.record(change);
+                self.status_message = Some(message);
+                if self.list_options.category.as_ref() == Some(&category) { // Keep showing the same to_dos
+                    self.list_options.category = Some(new_name.clone());
+                }
+                if let Some(position) = self.collapsed.iter().position(|collapsed| *collapsed == category) {
+                    self.collapsed[position] = new_name;
+                }
+                self.invalidate_rows();
+            }
+            Err(error) => self.status_message = Some(error.to_string()),
+        }
+    }
+
+    fn select_next(&mut self) {
+        let amount = self.rows().len();
+        self.todo_list_state.select(match self.todo_list_state.selected() {
+            Some(selected) if selected + 1 < amount => Some(selected + 1),
+            _ => Some(0),
+        });
+    }
+
+    fn select_previous(&mut self) {
+        let amount = self.rows().len();
+        self.todo_list_state.select(match self.todo_list_state.selected() {
+            Some(selected) if selected > 0 => Some(selected - 1),
+            _ => Some(amount.saturating_sub(1)),
+        });
+    }
+
+    fn record(&mut self, change: Change) { // Add a saved change to the undo history
+        if let Err(error) = record_change(change) {
+            self.status_message = Some(format!("Could not save the undo history: {}", error));
+        }
+    }
+
+    fn selected_trash_index(&self) -> Option<usize> { // Index of the selected trashed to_do in the store
+        trashed_indices(&self.store.todos).get(self.trash_list_state.selected()?).copied()
+    }
+
+    fn checklist(&self) -> &[ChecklistItem] { // Checklist of the selected to_do
+        match self.selected_index() {
+            Some(index) => &self.store.todos[index].checklist,
+            None => &[],
+        }
+    }
+
+    fn select_next_checklist_item(&mut self) {
+        let amount = self.checklist().len();
+        self.checklist_state.select(match self.checklist_state.selected() {
+            Some(selected) if selected + 1 < amount => Some(selected + 1),
+            _ => Some(0),
+        });
+    }
+
+    fn select_previous_checklist_item(&mut self) {
+        let amount = self.checklist().len();
+        self.checklist_state.select(match self.checklist_state.selected() {
+            Some(selected) if selected > 0 => Some(selected - 1),
+            _ => Some(amount.saturating_sub(1)),
+        });
+    }
+
+    fn selected_checklist_item(&self) -> Option<usize> {
+        self.checklist_state.selected().filter(|&item| item < self.checklist().len())
+    }
+
+    fn change_checklist(&mut self, change: impl FnOnce(&mut Vec<ChecklistItem>)) { // Edit the checklist of the selected to_do
+        if let Some(index) = self.selected_index() {
+            let id = self.store.todos[index].id;
+            if let Ok(change) = change_checklist_with_id(&mut self.store, id, change) {
+                if self.save(&change) {
+                    self.record(change);
+                }
+            }
+        }
+    }
+
+    fn toggle_checklist_item(&mut self) {
+        if let Some(item) = self.selected_checklist_item() {
+            self.change_checklist(|items| items[item].checked = !items[item].checked);
+        }
+    }
+
+    fn remove_checklist_item(&mut self) {
+        if let Some(item) = self.selected_checklist_item() {
+            self.change_checklist(|items| { items.remove(item); });
+            self.checklist_state.select(Some(item.min(self.checklist().len().saturating_sub(1))));
+        }
+    }
+
+    fn move_checklist_item(&mut self, down: bool) {
+        let Some(item) = self.selected_checklist_item() else {
+            return;
+        };
+        let to = if down { item + 1 } else { item.wrapping_sub(1) };
+        if to < self.checklist().len() {
+            self.change_checklist(|items| items.swap(item, to));
+            self.checklist_state.select(Some(to));
+        }
+    }
+
+    fn save_checklist_item(&mut self) { // Store the typed text as a new item or in the edited one
+        let text = self.checklist_input.trim().to_owned();
+        self.focused_input = FocusedInput::Checklist;
+        if text.is_empty() {
+            return;
+        }
+        match self.checklist_editing {
+            Some(item) if item < self.checklist().len() => self.change_checklist(|items| items[item].text = text),
+            Some(_) => {}
+            None => {
+                let added = self.checklist().len();
+                self.change_checklist(|items| items.push(ChecklistItem { text, checked: false }));
+                self.checklist_state.select(Some(added));
+            }
+        }
+    }
+
+    fn select_next_trash(&mut self) {
+        let amount = trashed_indices(&self.store.todos).len();
+        self.trash_list_state.select(match self.trash_list_state.selected() {
+            Some(selected) if selected + 1 < amount => Some(selected + 1),
+            _ => Some(0),
+        });
+    }
+
+    fn select_previous_trash(&mut self) {
+        let amount = trashed_indices(&self.store.todos).len();
+        self.trash_list_state.select(match self.trash_list_state.selected() {
+            Some(selected) if selected > 0 => Some(selected - 1),
+            _ => Some(amount.saturating_sub(1)),
+        });
+    }
+
+    fn change_selected_trash(&mut self, change: fn(&mut Store, usize) -> Result<Change, Error>) {
+        if let Some(index) = self.selected_trash_index() { // Restore or purge the selected to_do
+            let id = self.store.todos[index].id;
+            if let Ok(change) = change(&mut self.store, id) {
+                if self.save(&change) {
+                    self.record(change);
+                }
+            }
+            let selected = self.trash_list_state.selected().unwrap_or(0);
+            self.trash_list_state.select(Some(selected.saturating_sub(1)));
+        }
+    }
+
+    fn remove_selected(&mut self) { // Move the selected to_do to the trash
+        if let Some(index) = self.selected_index() {
+            let id = self.store.todos[index].id;
+            if let Ok(change) = trash_todo_with_id(&mut self.store, id) {
+                if self.save(&change) {
+                    self.record(change);
+                }
+            }
+            let selected = self.todo_list_state.selected().unwrap_or(0);
+            self.todo_list_state.select(Some(selected.saturating_sub(1)));
+        }
+    }
+
+    fn change_selected_status(&mut self, change: fn(TodoStatus) -> TodoStatus) {
+        if let Some(index) = self.selected_index() {
+            let todo = &self.store.todos[index];
+            let (id, status) = (todo.id, change(todo.status));
+            let Ok(change) = change_status_with_id(&mut self.store, id, status) else {
+                return;
+            };
+            if self.save(&change) {
+                if let Some(next) = change.added().first() {
+                    self.status_message = Some(match next.due {
+                        Some(due) => format!("Added the next one, {}", format_countdown(due, Utc::now())),
+                        None => "Added the next one".to_owned(),
+                    });
+                }
+                self.record(change);
+            }
+        }
+    }
+
+    fn move_selected(&mut self, target: fn(usize, usize) -> usize) { // Move the selected to_do in the manual order
+        if self.list_options.sort.first().is_some_and(|&key| key != SortKey::Manual) {
+            self.status_message = Some("Press 'o' until the TODOs are in manual order to move them".to_owned());
+            return;
+        }
+        let Some((siblings, from)) = self.selected_siblings() else { // Subtasks move among each other
+            return;
+        };
+        let index = siblings[from];
+        let to = target(from, siblings.len());
+        if to == from || to >= siblings.len() {
+            return;
+        }
+
+        // Place the to_do next to the one it moves past, hidden to_dos keep their places
+        let mut order: Vec<usize> = (0..self.store.todos.len()).collect();
+        order.sort_by_key(|&other| self.store.todos[other].position);
+        let before: Vec<(usize, usize)> = self.store.todos.iter().map(|todo| (todo.id, todo.position)).collect();
+        order.retain(|&other| other != index);
+        let neighbour = order.iter().position(|&other| other == siblings[to]).unwrap_or(0);
+        order.insert(if to > from { neighbour + 1 } else { neighbour }, index);
+        for (position, &other) in order.iter().enumerate() {
+            self.store.todos[other].position = position;
+        }
+
+        let after = self.store.todos.iter().map(|todo| (todo.id, todo.position)).collect();
+        let change = Change::Moved { todo: self.store.todos[index].clone(), before, after };
+        if self.save(&change) {
+            self.record(change);
+            if let Some(row) = self.row_of(self.store.todos[index].id) {
+                self.todo_list_state.select(Some(row));
+            }
+        }
+    }
+
+    fn undo(&mut self) { // Revert the most recent change, also ones from earlier sessions
+        let mut history = read_history();
+        let change = match history.undo.pop() {
+            Some(change) => change,
+            None => {
+                self.status_message = Some("Nothing to undo".to_owned());
+                return;
+            }
+        };
+        let inverse = change.inverse();
+        self.status_message = Some(match inverse.apply(&mut self.store) {
+            Ok(()) if !self.save(&inverse) => return,
+            Ok(()) => {
+                let message = format!("Undid {}", change.describe());
+                history.redo.push(change);
+                message
+            }
+            Err(error) => format!("Could not undo {}: {}", change.describe(), error),
+        });
+        if let Err(error) = write_history(&history) {
+            self.status_message = Some(format!("Could not save the undo history: {}", error));
+        }
+    }
+
+    fn redo(&mut self) { // Apply the most recently undone change again
+        let mut history = read_history();
+        let change = match history.redo.pop() {
+            Some(change) => change,
+            None => {
+                self.status_message = Some("Nothing to redo".to_owned());
+                return;
+            }
+        };
+        self.status_message = Some(match change.apply(&mut self.store) {
+            Ok(()) if !self.save(&change) => return,
+            Ok(()) => {
+                let message = format!("Redid {}", change.describe());
+                history.undo.push(change);
+                message
+            }
+            Err(error) => format!("Could not redo {}: {}", change.describe(), error),
+        });
+        if let Err(error) = write_history(&history) {
+            self.status_message = Some(format!("Could not save the undo history: {}", error));
+        }
+    }
+}
+
+
+#[derive(Deserialize, Default)]
+struct Config { // Holds the settings read from the config file
+    #[serde(default)]
+    db_path: Option<PathBuf>,
+    #[serde(default)]
+    backups: Option<usize>,
+    #[serde(default)]
+    trash_days: Option<i64>,
+    #[serde(default)]
+    views: Vec<SavedView>,
+    #[serde(default)]
+    theme: Theme,
+}
+
+
+#[derive(Deserialize, Default)]
+struct Theme { // Holds the colors set in the config
+    #[serde(default)]
+    tags: HashMap<String, ThemeColor>, // By tag, the others use TAG_COLOR
+}
+
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "String")]
+struct ThemeColor(Color); // A color name like "light-blue" or "#ff8800"
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<ThemeColor, String> {
+        parse_color(&name).map(ThemeColor).ok_or_else(|| format!("unknown color '{}'", name))
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Default)]
+struct UiState { // Holds interface settings that are kept between sessions
+    #[serde(default)]
+    sort: Vec<SortKey>,
+    #[serde(default)]
+    group: bool, // Whether the TODOs tab groups the to_dos by category
+}
+
+
+#[derive(Deserialize, Clone)]
+struct SavedView { // Holds a named filter from the config, listed next to the TODOs
+    name: String,
+    filter: String, // Query as understood by parse_filter()
+}
+
+impl SavedView {
+    fn parse(&self, now: DateTime<Local>) -> Result<Vec<Filter>, Error> {
+        parse_filter(&self.filter, now)
+    }
+}
+
+
+#[derive(Clone)]
+struct ListOptions { // Holds the settings that control which to_dos are listed and how
+    show_completed: bool,
+    sort: Vec<SortKey>, // Primary key first, ties keep the order of the db
+    search: String, // Query typed into the search prompt
+    filter: Vec<Filter>, // The search as parsed by parse_filter(), only matching to_dos are listed
+    view: usize, // Selected saved view, 0 lists all to_dos and n the n-th view of the config
+    category: Option<String>, // Only list to_dos of this uppercased category, "" for the ones without
+    group: bool, // List the to_dos under a header for each category
+}
+
+impl ListOptions {
+    fn view(&self) -> Option<&'static SavedView> { // The selected saved view, None for all to_dos
+        config().views.get(self.view.checked_sub(1)?)
+    }
+}
+
+
+struct ListCounts { // Holds the numbers of listed to_dos shown next to the views, categories and tags
+    views: Vec<Result<usize, String>>, // All to_dos first, then each saved view or why it can not be shown
+    categories: Vec<(String, usize)>, // Each category as listed by categories(), with the search and view
+    all_categories: usize,
+    tags: Vec<(String, usize)>, // Each tag as listed by tags(), with everything the list uses
+}
+// ----------------------------------        END OF STRUCTS       ----------------------------------
+
+
+// ----------------------------------            ENUMS            ----------------------------------
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+enum TodoStatus { // Holds the completion status of a to_do
+    #[default]
+    Open,
+    InProgress,
+    Done,
+    Cancelled
+}
+
+impl TodoStatus {
+    const ALL: [TodoStatus; 4] = [
+        TodoStatus::Open, TodoStatus::InProgress, TodoStatus::Done, TodoStatus::Cancelled
+    ];
+
+    fn from_label(label: &str) -> Option<TodoStatus> { // Also takes "in-progress"
+        let label = label.to_lowercase().replace('-', " ");
+        TodoStatus::ALL.iter().copied().find(|status| status.label() == label)
+    }
+
+    fn next(self) -> TodoStatus { // Cycle through all statuses
+        match self {
+            TodoStatus::Open => TodoStatus::InProgress,
+            TodoStatus::InProgress => TodoStatus::Done,
+            TodoStatus::Done => TodoStatus::Cancelled,
+            TodoStatus::Cancelled => TodoStatus::Open,
+        }
+    }
+
+    fn toggled_done(self) -> TodoStatus { // Flip between done and open
+        match self {
+            TodoStatus::Done => TodoStatus::Open,
+            _ => TodoStatus::Done,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TodoStatus::Open => "open",
+            TodoStatus::InProgress => "in progress",
+            TodoStatus::Done => "done",
+            TodoStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn marker(self) -> &'static str { // Checkbox shown in front of the to_do name
+        match self {
+            TodoStatus::Open => "[ ] ",
+            TodoStatus::InProgress => "[~] ",
+            TodoStatus::Done => "[x] ",
+            TodoStatus::Cancelled => "[-] ",
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+enum Priority { // Holds the importance of a to_do, from lowest to highest
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent
+}
+
+impl Priority {
+    const ALL: [Priority; 5] = [
+        Priority::None, Priority::Low, Priority::Medium, Priority::High, Priority::Urgent
+    ];
+
+    fn raised(self) -> Priority {
+        match self {
+            Priority::None => Priority::Low,
+            Priority::Low => Priority::Medium,
+            Priority::Medium => Priority::High,
+            Priority::High | Priority::Urgent => Priority::Urgent,
+        }
+    }
+
+    fn lowered(self) -> Priority {
+        match self {
+            Priority::None | Priority::Low => Priority::None,
+            Priority::Medium => Priority::Low,
+            Priority::High => Priority::Medium,
+            Priority::Urgent => Priority::High,
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Priority> {
+        Priority::ALL.iter().copied().find(|level| level.label() == label.to_lowercase())
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Priority::None => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+
+    fn color(self) -> Color { // Color used for list items of this priority
+        match self {
+            Priority::None => ACTIVE_COLOR,
+            Priority::Low => Color::Green,
+            Priority::Medium => Color::Yellow,
+            Priority::High => Color::LightRed,
+            Priority::Urgent => Color::Red,
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+enum Recurrence { // Holds when a recurring to_do is due again
+    Daily,
+    Weekly { weekdays: Vec<Weekday> }, // Sorted from Monday on
+    Monthly { day: u32 }, // Shorter months use their last day
+    AfterCompletion { days: u32 },
+}
+
+impl Recurrence {
+    fn describe(&self) -> String { // The rule in plain English, parse_recurrence() reads it back
+        match self {
+            Recurrence::Daily => "every day".to_owned(),
+            Recurrence::Weekly { weekdays } => format!(
+                "every week on {}",
+                join_words(&weekdays.iter().map(|&weekday| weekday_name(weekday)).collect::<Vec<_>>())
+            ),
+            Recurrence::Monthly { day } => format!("every month on the {}", ordinal(*day)),
+            Recurrence::AfterCompletion { days: 1 } => "every day after completion".to_owned(),
+            Recurrence::AfterCompletion { days } => format!("every {} days after completion", days),
+        }
+    }
+
+    fn next_due(&self, due: Option<DateTime<Utc>>, completed_at: DateTime<Utc>)
+        -> Option<DateTime<Utc>> { // When the next occurrence is due, at the same time of day as this one
+        let completed = completed_at.with_timezone(&Local);
+        let due = due.map(|due| due.with_timezone(&Local));
+        let after = due.map_or(completed, |due| due.max(completed)).date_naive(); // Occurrences missed are skipped
+        let date = match self {
+            Recurrence::Daily => after + Duration::days(1),
+            Recurrence::Weekly { weekdays } => (1..=7)
+                .map(|days| after + Duration::days(days))
+                .find(|date| weekdays.contains(&date.weekday()))?,
+            Recurrence::Monthly { day } => match day_of_month(after.year(), after.month(), *day)? {
+                date if date > after => date,
+                _ if after.month() == 12 => day_of_month(after.year() + 1, 1, *day)?,
+                _ => day_of_month(after.year(), after.month() + 1, *day)?,
+            },
+            Recurrence::AfterCompletion { days } => completed.date_naive() + Duration::days((*days).into()),
+        };
+        let next = match due {
+            Some(due) => Local.from_local_datetime(&date.and_time(due.time())).earliest(),
+            None => end_of_day(date),
+        };
+        next.map(|next| next.with_timezone(&Utc))
+    }
+}
+
+
+#[derive(PartialEq, Clone, Copy)]
+enum FocusedInput { // Holds the current focused input
+    Name,
+    Category,
+    Tags,
+    Priority,
+    Due,
+    Repeat,
+    BlockedBy,
+    Text,
+    Search, // The search prompt of the TODOs tab
+    Checklist, // The checklist of the selected to_do
+    ChecklistItem, // The text of a checklist item
+    Rename, // The new name of a category
+    None
+}
+
+
+#[derive(Clone, Copy)]
+enum OutputFormat { // Holds how list and show print to_dos
+    Table,
+    Json,
+    Tsv
+}
+
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum SortKey { // Holds what the TODOs tab is sorted by
+    Manual,
+    Created,
+    Name,
+    Category,
+    Priority,
+    Due,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 6] = [
+        SortKey::Manual, SortKey::Created, SortKey::Name, SortKey::Category, SortKey::Priority, SortKey::Due
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Manual => "manual order",
+            SortKey::Created => "created",
+            SortKey::Name => "name",
+            SortKey::Category => "category",
+            SortKey::Priority => "priority",
+            SortKey::Due => "due date",
+        }
+    }
+
+    fn next(self) -> SortKey {
+        let index = SortKey::ALL.iter().position(|&key| key == self).unwrap_or(0);
+        SortKey::ALL[(index + 1) % SortKey::ALL.len()]
+    }
+
+    fn compare(self, a: &Todo, b: &Todo) -> std::cmp::Ordering {
+        match self {
+            SortKey::Manual => a.position.cmp(&b.position),
+            SortKey::Created => a.created_at.cmp(&b.created_at),
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Category => a.category.to_lowercase().cmp(&b.category.to_lowercase()),
+            SortKey::Priority => b.priority.cmp(&a.priority), // Most important first
+            SortKey::Due => a.due.is_none().cmp(&b.due.is_none()).then(a.due.cmp(&b.due)), // Soonest first
+        }
+    }
+}
+
+
+enum Command { // Holds what was asked for on the command line
+    Tui,
+    Add(InputStates),
+    List(Vec<Filter>, OutputFormat),
+    Show(usize, OutputFormat),
+    Remove(usize),
+    Done(usize, bool), // Also mark the subtasks as done
+    Trash(Vec<Filter>, OutputFormat),
+    Restore(usize),
+    Purge(usize),
+    Repair(bool),
+    Categories,
+    RenameCategory(String, String),
+    Migrate,
+}
+
+impl Command {
+    fn writes(&self) -> bool { // Whether the command changes the db
+        matches!(
+            self,
+            Command::Add(_) | Command::Remove(_) | Command::Done(..) | Command::Restore(_) | Command::Purge(_)
+            | Command::Repair(_) | Command::RenameCategory(..) | Command::Migrate
+        )
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Change { // Holds one reversible mutation of the store
+    Added { index: usize, todo: Todo },
+    Removed { index: usize, todo: Todo },
+    Edited { before: Todo, after: Todo },
+    StatusChanged { before: Todo, after: Todo },
+    Trashed { before: Todo, after: Todo },
+    Restored { before: Todo, after: Todo },
+    Moved { todo: Todo, before: Vec<(usize, usize)>, after: Vec<(usize, usize)> }, // Ids and positions
+    Batch { description: String, changes: Vec<Change> }, // Undone and redone together
+}
+
+impl Change {
+    fn added(&self) -> Vec<&Todo> { // To_dos the change adds, like the next occurrence of a recurring one
+        match self {
+            Change::Added { todo, .. } => vec![todo],
+            Change::Batch { changes, .. } => changes.iter().flat_map(Change::added).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn todo(&self) -> Option<&Todo> { // The to_do as it is after the change, None if several changed
+        match self {
+            Change::Added { todo, .. } | Change::Removed { todo, .. } | Change::Moved { todo, .. } => Some(todo),
+            Change::Edited { after, .. }
+            | Change::StatusChanged { after, .. }
+            | Change::Trashed { after, .. }
+            | Change::Restored { after, .. } => Some(after),
+            Change::Batch { .. } => None,
+        }
+    }
+
+    fn describe(&self) -> String { // Shown in the status line after undo and redo
+        match self {
+            Change::Added { todo, .. } => format!("adding '{}'", todo.name),
+            Change::Removed { todo, .. } => format!("purging '{}'", todo.name),
+            Change::Edited { after, .. } => format!("editing '{}'", after.name),
+            Change::StatusChanged { after, .. } => {
+                format!("marking '{}' as {}", after.name, after.status.label())
+            }
+            Change::Trashed { after, .. } => format!("trashing '{}'", after.name),
+            Change::Restored { after, .. } => format!("restoring '{}'", after.name),
+            Change::Moved { todo, .. } => format!("moving '{}'", todo.name),
+            Change::Batch { description, .. } => description.clone(),
+        }
+    }
+
+    fn inverse(&self) -> Change { // The change that reverts this one
+        match self.clone() {
+            Change::Added { index, todo } => Change::Removed { index, todo },
+            Change::Removed { index, todo } => Change::Added { index, todo },
+            Change::Edited { before, after } => Change::Edited { before: after, after: before },
+            Change::StatusChanged { before, after } => {
+                Change::StatusChanged { before: after, after: before }
+            }
+            Change::Trashed { before, after } => Change::Trashed { before: after, after: before },
+            Change::Restored { before, after } => Change::Restored { before: after, after: before },
+            Change::Moved { todo, before, after } => Change::Moved { todo, before: after, after: before },
+            Change::Batch { description, changes } => Change::Batch {
+                description,
+                changes: changes.iter().rev().map(Change::inverse).collect(),
+            },
+        }
+    }
+
+    fn apply(&self, store: &mut Store) -> Result<(), Error> { // Make the change to a store
+        match self {
+            Change::Added { index, todo } => {
+                if store.todos.iter().any(|other| other.id == todo.id) {
+                    return Err(Error::DuplicateId(todo.id));
+                }
+                store.todos.insert((*index).min(store.todos.len()), todo.clone());
+                store.next_id = store.next_id.max(todo.id + 1);
+            }
+            Change::Removed { todo, .. } => {
+                remove_todo_with_id(store, todo.id)?;
+            }
+            Change::Moved { after, .. } => { // To_dos removed since then simply stay gone
+                for todo in store.todos.iter_mut() {
+                    if let Some((_, position)) = after.iter().find(|(id, _)| *id == todo.id) {
+                        todo.position = *position;
+                    }
+                }
+            }
+            Change::Batch { changes, .. } => { // All or nothing, so a failed undo leaves the store alone
+                let mut changed = store.clone();
+                for change in changes {
+                    change.apply(&mut changed)?;
+                }
+                *store = changed;
+            }
+            Change::Edited { after, .. }
+            | Change::StatusChanged { after, .. }
+            | Change::Trashed { after, .. }
+            | Change::Restored { after, .. } => {
+                let todo = store.todos
+                    .iter_mut()
+                    .find(|todo| todo.id == after.id)
+                    .ok_or(Error::TodoNotFound(after.id))?;
+                *todo = after.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Clone, Debug)]
+enum Filter { // Holds one term of a filter query, a query matches when all its terms do
+    Not(Box<Filter>),
+    Word(String), // Fuzzy match in name, category, tags and text
+    Id(usize),
+    Name(TextMatch),
+    Category(TextMatch),
+    Tag(TextMatch), // Matches when one of the tags does
+    Text(TextMatch),
+    Status(TodoStatus),
+    Priority(std::cmp::Ordering, Priority), // Compares the priority of the to_do with the given one
+    Date(DateField, Option<DateTime<Utc>>, Option<DateTime<Utc>>), // Set and in [from, until)
+    NoDate(DateField),
+    Ready, // Open with all blockers done
+    Blocked, // Waits for a to_do that is not done
+}
+
+impl Filter {
+    fn matches(&self, todo: &Todo, todos: &[Todo], ids: &HashMap<usize, usize>)
+        -> bool { // The blockers of a to_do are looked up in todos, see index_by_id()
+        match self {
+            Filter::Not(filter) => !filter.matches(todo, todos, ids),
+            Filter::Word(word) => [&todo.name, &todo.category, &todo.text]
+                .into_iter()
+                .chain(&todo.tags)
+                .any(|field| fuzzy_match(field, word).is_some()),
+            Filter::Id(id) => todo.id == *id,
+            Filter::Name(text_match) => text_match.matches(&todo.name),
+            Filter::Category(text_match) => text_match.matches(&todo.category),
+            Filter::Tag(text_match) => todo.tags.iter().any(|tag| text_match.matches(tag)),
+            Filter::Text(text_match) => text_match.matches(&todo.text),
+            Filter::Status(status) => todo.status == *status,
+            Filter::Priority(ordering, priority) => todo.priority.cmp(priority) == *ordering,
+            Filter::Date(field, from, until) => field.value(todo).is_some_and(|date| {
+                from.is_none_or(|from| date >= from) && until.is_none_or(|until| date < until)
+            }),
+            Filter::NoDate(field) => field.value(todo).is_none(),
+            Filter::Ready => !todo.is_completed() && !is_blocked(todos, ids, todo),
+            Filter::Blocked => is_blocked(todos, ids, todo),
+        }
+    }
+
+    fn highlight(&self) -> Option<&str> { // Text to highlight in the names of matching to_dos
+        match self {
+            Filter::Word(word) => Some(word),
+            Filter::Name(text_match) => Some(text_match.text()),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+enum TextMatch { // Holds how a filter compares a text field, always ignoring case
+    Exact(String),
+    Contains(String),
+    Fuzzy(String),
+}
+
+impl TextMatch {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            TextMatch::Exact(text) => value.to_lowercase() == text.to_lowercase(),
+            TextMatch::Contains(text) => value.to_lowercase().contains(&text.to_lowercase()),
+            TextMatch::Fuzzy(text) => fuzzy_match(value, text).is_some(),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            TextMatch::Exact(text) | TextMatch::Contains(text) | TextMatch::Fuzzy(text) => text,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+enum DateField { // Holds which date of a to_do a filter looks at
+    Created,
+    Due,
+    Completed,
+    Updated,
+}
+
+impl DateField {
+    fn from_name(name: &str) -> Option<DateField> {
+        match name {
+            "created" => Some(DateField::Created),
+            "due" => Some(DateField::Due),
+            "completed" => Some(DateField::Completed),
+            "updated" => Some(DateField::Updated),
+            _ => None,
+        }
+    }
+
+    fn value(self, todo: &Todo) -> Option<DateTime<Utc>> {
+        match self {
+            DateField::Created => Some(todo.created_at),
+            DateField::Due => todo.due,
+            DateField::Completed => todo.completed_at,
+            DateField::Updated => todo.updated_at,
+        }
+    }
+}
+
+
+enum ListRow { // Holds one line of the TODOs list
+    Group { category: String, count: usize, collapsed: bool }, // Header above the to_dos of a category
+    Todo { // A to_do in the store
+        index: usize,
+        depth: usize, // Number of listed parents
+        folded: bool,
+        subtasks: (usize, usize), // Completed and all subtasks outside the trash
+        blocked: bool, // Waits for a to_do that is not done
+    },
+}
+
+
+enum Event<I> {
+    Input(I),
+    Tick,
+    Changed // The db file was changed on disk
+}
+
+
+#[derive(Error, Debug)]
+pub enum Error { // See USAGE for the exit code of each variant
+    #[error("error reading the DB file: {0}")]
+    ReadDBError(#[from] io::Error),
+    #[error("error parsing the DB file: {0}")]
+    ParseDBError(#[from] serde_json::Error),
+    #[error("the DB file is corrupt and will not be written to")]
+    CorruptDBError,
+    #[error("the DB file can not be read and will not be written to")]
+    UnreadableDBError,
+    #[error("unrecognised due date: {0}")]
+    ParseDueError(String),
+    #[error("error parsing the config file {0}: {1}")]
+    ParseConfigError(PathBuf, serde_json::Error),
+    #[error("there is no TODO with id {0}")]
+    TodoNotFound(usize),
+    #[error("there already is a TODO with id {0}")]
+    DuplicateId(usize),
+    #[error("invalid filter at column {0}: {1}")]
+    ParseFilterError(usize, String),
+    #[error("TODOs of an older version are still in {0}, run `todo-cli migrate` to move them first")]
+    LegacyDBFound(PathBuf),
+    #[error("there is no TODO in category {0}")]
+    CategoryNotFound(String),
+    #[error("unrecognised TODO id: {0}")]
+    ParseIdError(String),
+    #[error("unrecognised repeat rule: {0}")]
+    ParseRecurrenceError(String),
+    #[error("TODO {0} can not be blocked by TODO {1}, that would make them wait for each other")]
+    DependencyCycle(usize, usize),
+    #[error("error writing the output: {0}")]
+    WriteOutputError(io::Error),
+}
+
+impl Error {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Error::ParseDueError(_) | Error::ParseFilterError(..) | Error::ParseIdError(_)
+            | Error::ParseRecurrenceError(_) => 2,
+            Error::TodoNotFound(_) | Error::CategoryNotFound(_) => 3,
+            Error::ParseDBError(_) | Error::CorruptDBError => 4,
+            Error::ReadDBError(_) | Error::UnreadableDBError => 5,
+            Error::ParseConfigError(..) | Error::DuplicateId(_) | Error::LegacyDBFound(_)
+            | Error::DependencyCycle(..) | Error::WriteOutputError(_) => 1,
+        }
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MenuItem { // Holds the menu tabs that can be opened
+    Home,
+    TODOs,
+    Add,
+    Backups,
+    Trash,
+    Recovery
+}
+
+impl From<MenuItem> for usize {
+    fn from(input: MenuItem) -> usize {
+        match input {
+            MenuItem::Home => 0,
+            MenuItem::TODOs => 1,
+            MenuItem::Add => 2,
+            MenuItem::Backups => 4,
+            MenuItem::Trash => 5,
+            MenuItem::Recovery => 7,
+        }
+    }
+}
+// ----------------------------------         END OF ENUMS        ----------------------------------
+
+
+// ----------------------------------      UI BLOCK FUNCTIONS     ----------------------------------
+fn copyright_block<'a>(status_message: &Option<String>) -> Paragraph<'a> { // Render the fake copyright block
+    match status_message { // Status messages take the place of the copyright notice
+        Some(message) => Paragraph::new(message.clone())
+            .style(Style::default().fg(Color::Yellow))
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(ACTIVE_COLOR))
+                    .title("Status")
+                    .border_type(DEFAULT_BORDER)
+            ),
+        None => Paragraph::new("todo-CLI 2023 --- all rights reserved")
+            .style(Style::default().fg(FOCUS_COLOR))
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(ACTIVE_COLOR))
+                    .title("Copyright")
+                    .border_type(DEFAULT_BORDER)
+            ),
+    }
+}
+
+
+fn render_add<'a>(input_states: &InputStates, focused_input: &FocusedInput, // Render the Add tab
+                  editing: Option<usize>)
+                  -> (Paragraph<'a>, Paragraph<'a>, Paragraph<'a>, Paragraph<'a>, Paragraph<'a>,
+                      Paragraph<'a>, Paragraph<'a>, Paragraph<'a>, Paragraph<'a>) {
+
+    // Draw help text
+    let help_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(DEFAULT_BORDER)
+        .style(Style::default().fg(ACTIVE_COLOR))
+        .title(match (editing, input_states.parent) {
+            (Some(id), _) => format!("Help - editing TODO {}", id),
+            (None, Some(parent)) => format!("Help - adding a subtask to TODO {}", parent),
+            (None, None) => "Help".to_string(),
+        });
+
+    let help =
+        Paragraph::new(match editing {
+            Some(_) => "Use <tab> to switch between fields, <left>/<right> or 0-4 to set \
+            the priority, <enter> to save changes, <esc> twice to cancel editing",
+            None => "Use <tab> to switch between fields, <left>/<right> or 0-4 to set \
+            the priority, <enter> to submit",
+        })
+        .block(help_block)
+        .style(Style::default().fg(FOCUS_COLOR));
+
+    // Create the blocks
+    let text_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(ACTIVE_COLOR))
+        .title("Text")
+        .border_type(
+            if focused_input == &FocusedInput::Text {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if focused_input == &FocusedInput::Text {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let name_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title("Name")
+        .border_type(
+            if focused_input == &FocusedInput::Name {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if focused_input == &FocusedInput::Name {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let category_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title("Category")
+        .border_type(
+            if focused_input == &FocusedInput::Category {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if focused_input == &FocusedInput::Category {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let tags_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title("Tags")
+        .border_type(
+            if focused_input == &FocusedInput::Tags {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if focused_input == &FocusedInput::Tags {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let priority_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title("Priority")
+        .border_type(
+            if focused_input == &FocusedInput::Priority {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if focused_input == &FocusedInput::Priority {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let due_is_valid = parse_due(&input_states.due, Local::now()).is_ok();
+    let due_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title(if due_is_valid { "Due" } else { "Due (not understood)" })
+        .border_type(
+            if focused_input == &FocusedInput::Due {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if !due_is_valid {
+                Color::Red
+            } else if focused_input == &FocusedInput::Due {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let repeat_is_valid = parse_recurrence(&input_states.repeat).is_ok();
+    let repeat_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title(if repeat_is_valid { "Repeat" } else { "Repeat (not understood)" })
+        .border_type(
+            if focused_input == &FocusedInput::Repeat {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if !repeat_is_valid {
+                Color::Red
+            } else if focused_input == &FocusedInput::Repeat {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    let blocked_by_is_valid = parse_ids(&input_states.blocked_by).is_ok();
+    let blocked_by_block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Gray))
+        .title(if blocked_by_is_valid { "Blocked By" } else { "Blocked By (not understood)" })
+        .border_type(
+            if focused_input == &FocusedInput::BlockedBy {
+                FOCUS_BORDER
+            } else {
+                DEFAULT_BORDER
+            }
+        )
+        .border_style(Style::default().fg(
+            if !blocked_by_is_valid {
+                Color::Red
+            } else if focused_input == &FocusedInput::BlockedBy {
+                FOCUS_COLOR
+            } else {
+                ACTIVE_COLOR
+            }
+        ));
+
+    // Draw the name field
+    let name = Paragraph::new("Name for a TODO: ".to_owned() + &input_states.name)
+        .block(name_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Name {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the category field
+    let category = Paragraph::new("Category for a TODO: ".to_owned() + &input_states.category)
+        .block(category_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Category {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the tags field
+    let tags = Paragraph::new(
+        "Tags, #tag words in the other fields count as well: ".to_owned() + &input_states.tags
+    )
+        .block(tags_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Tags {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the priority field
+    let priority = Paragraph::new(Spans::from(
+        Priority::ALL
+            .iter()
+            .map(|&level| {
+                let style = Style::default().fg(level.color());
+                if level == input_states.priority {
+                    Span::styled(format!("[{}] ", level.label()),
+                                 style.add_modifier(Modifier::BOLD))
+                } else {
+                    Span::styled(format!(" {}  ", level.label()), style)
+                }
+            })
+            .collect::<Vec<_>>()
+    ))
+        .block(priority_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Priority {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the due field
+    let due = Paragraph::new(
+        "Due date (2023-05-01, tomorrow, next fri, in 3 days, eod): ".to_owned() + &input_states.due
+    )
+        .block(due_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Due {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the repeat field
+    let repeat = Paragraph::new(
+        "Repeat (daily, weekly on mon fri, monthly on 15, every 3 days after completion): ".to_owned()
+            + &input_states.repeat
+    )
+        .block(repeat_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Repeat {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the blocked by field
+    let blocked_by = Paragraph::new(
+        "Ids of the TODOs that have to be done first (3, 12): ".to_owned() + &input_states.blocked_by
+    )
+        .block(blocked_by_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::BlockedBy {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    // Draw the Text field
+    let text = Paragraph::new("Text for a TODO: ".to_owned() + &input_states.text)
+        .block(text_block)
+        .style(Style::default().fg(
+            if focused_input == &FocusedInput::Text {
+                ACTIVE_COLOR
+            } else {
+                INACTIVE_COLOR
+            }
+        ));
+
+    (help, name, category, tags, priority, due, repeat, blocked_by, text)
+}
+
+
+fn render_todos<'a>(todo_list: &[Todo], rows: &[ListRow], todo_list_state: &ListState,
+                    list_options: &ListOptions) -> (List<'a>, Table<'a>) { // render TODOs tab
+
+    // Create a block for displaying TODOs
+    let todos = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(ACTIVE_COLOR))
+        .title(format!(
+            "{}{}{}{}",
+            list_options.view().map_or("TODOs", |view| view.name.as_str()),
+            list_options.category.as_ref().map_or_else(String::new, |category| {
+                format!(" [{}]", category_label(category))
+            }),
+            if list_options.show_completed { "" } else { " (open)" },
+            match list_options.sort.first() {
+                None | Some(SortKey::Manual) => " in manual order".to_owned(),
+                Some(_) => format!(
+                    " by {}",
+                    list_options.sort.iter().map(|key| key.label()).collect::<Vec<_>>().join(", ")
+                ),
+            },
+        ))
+        .border_type(DEFAULT_BORDER);
+
+    // Create a list for navigation between TODOs
+    let now = Utc::now();
+    let items: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            let (index, depth, folded, (done, subtasks), blocked) = match row {
+                &ListRow::Todo { index, depth, folded, subtasks, blocked } => (index, depth, folded, subtasks, blocked),
+                ListRow::Group { category, count, collapsed } => { // Header of a category
+                    return ListItem::new(Spans::from(vec![Span::styled(
+                        format!("{} {} ({})", if *collapsed { "▸" } else { "▾" }, category_label(category), count),
+                        Style::default().fg(FOCUS_COLOR).add_modifier(Modifier::BOLD),
+                    )]));
+                }
+            };
+            let todo = &todo_list[index];
+            let style = if todo.is_completed() {
+                Style::default().fg(INACTIVE_COLOR).add_modifier(Modifier::CROSSED_OUT)
+            } else {
+                Style::default().fg(todo.priority.color())
+            };
+            let mut spans = vec![
+                Span::raw("  ".repeat(depth + usize::from(list_options.group))),
+                Span::raw(match (subtasks, folded) { // Subtasks can be folded away
+                    (0, _) => "",
+                    (_, true) => "▸ ",
+                    (_, false) => "▾ ",
+                }),
+                Span::raw(todo.status.marker()),
+            ];
+            spans.extend(highlight_matches(
+                &todo.name,
+                &search_positions(&todo.name, &list_options.filter),
+                style,
+            ));
+            if subtasks > 0 {
+                spans.push(Span::styled(format!(" {}/{}", done, subtasks), Style::default().fg(INACTIVE_COLOR)));
+            }
+            if let Some(percentage) = todo.checklist_percentage() {
+                spans.push(Span::styled(format!(" {}%", percentage), Style::default().fg(INACTIVE_COLOR)));
+            }
+            if todo.recurrence.is_some() {
+                spans.push(Span::styled(" ↻", Style::default().fg(INACTIVE_COLOR)));
+            }
+            if !todo.is_completed() && blocked {
+                spans.push(Span::styled(" blocked", Style::default().fg(BLOCKED_COLOR)));
+            }
+            for tag in &todo.tags_not_in(&[&todo.name]) { // The name shows its own #tag words
+                spans.push(Span::styled(format!(" #{}", tag), Style::default().fg(tag_color(tag))));
+            }
+            if todo.is_overdue(now) { // Highlight to_dos that need attention
+                spans.push(Span::styled(
+                    " overdue",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ));
+            } else if todo.is_due_today(now) {
+                spans.push(Span::styled(" today", Style::default().fg(Color::Yellow)));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let (selected_todo, blocked) = match todo_list_state.selected() { // Get the selected to_do
+        None => {
+            (Todo::default(), false)
+        }
+        Some(selection) => {
+            match rows.get(selection) {
+                Some(ListRow::Todo { index, blocked, .. }) => { (todo_list[*index].clone(), *blocked) }
+                _ => {(Todo::default(), false)}
+            }
+        }
+    };
+
+    // Put the list inside the block
+    let list = List::new(items).block(todos).highlight_style(
+        Style::default()
+            .bg(BG_HIGHLIGHT_COLOR)
+            .fg(FOCUS_COLOR)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let recurrence = selected_todo.recurrence.as_ref().map(Recurrence::describe);
+
+    // Show the due date as a countdown
+    let due_cell = Cell::from(Span::styled(
+        match selected_todo.due {
+            Some(due) => format_countdown(due, now),
+            None => "".to_string(),
+        },
+        Style::default().fg(
+            if selected_todo.is_overdue(now) {
+                Color::Red
+            } else if selected_todo.is_due_today(now) {
+                Color::Yellow
+            } else {
+                ACTIVE_COLOR
+            }
+        ),
+    ));
+
+    // Create a table
+    let todo_detail = Table::new(vec![Row::new(vec![
+        Cell::from(Span::raw(selected_todo.id.to_string())),
+        Cell::from(Span::raw(selected_todo.name)),
+        Cell::from(Span::raw(selected_todo.category)),
+        Cell::from(Spans::from(
+            selected_todo.tags
+                .iter()
+                .map(|tag| Span::styled(format!("#{} ", tag), Style::default().fg(tag_color(tag))))
+                .collect::<Vec<_>>()
+        )),
+        Cell::from(Span::raw(selected_todo.text)),
+        Cell::from(Span::styled(
+            selected_todo.priority.label(),
+            Style::default().fg(selected_todo.priority.color()),
+        )),
+        Cell::from(Span::raw(selected_todo.status.label())),
+        due_cell,
+        Cell::from(Span::styled(
+            format_ids(&selected_todo.blocked_by),
+            Style::default().fg(if blocked { BLOCKED_COLOR } else { ACTIVE_COLOR }),
+        )),
+        Cell::from(Span::raw(format_ids(&dependents(todo_list, selected_todo.id)))),
+        Cell::from(Span::raw(selected_todo.created_at.to_string())),
+    ])])
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "ID",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Name",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Category",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Tags",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Text",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Priority",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Status",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Due",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Blocked By",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Blocks",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Created At",
+                Style::default().add_modifier(Modifier::BOLD),
             )),
         ]))
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .style(Style::default().fg(ACTIVE_COLOR))
-                .title("Detail")
+                .title(recurrence.map_or_else(|| "Detail".to_owned(), |rule| format!("Detail - repeats {}", rule)))
+                .border_type(DEFAULT_BORDER),
+        )
+        .widths(&[
+            Constraint::Percentage(4),
+            Constraint::Percentage(12),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+            Constraint::Percentage(12),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(10),
+            Constraint::Percentage(9),
+            Constraint::Percentage(7),
+            Constraint::Percentage(12),
+        ]);
+
+    (list, todo_detail)
+}
+
+
+fn render_views<'a>(counts: &ListCounts) -> List<'a> { // Render the saved views with the number of to_dos each one lists
+    let names = ["All"].into_iter().chain(config().views.iter().map(|view| view.name.as_str()));
+    let items: Vec<ListItem> = names
+        .zip(&counts.views)
+        .map(|(name, count)| ListItem::new(Spans::from(vec![match count {
+            Ok(count) => Span::raw(format!("{} ({})", name, count)),
+            Err(error) => Span::styled(format!("{} ({})", name, error), Style::default().fg(Color::Red)),
+        }])))
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Views")
+                .border_type(DEFAULT_BORDER),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(BG_HIGHLIGHT_COLOR)
+                .fg(FOCUS_COLOR)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+
+fn render_checklist<'a>(checklist: &[ChecklistItem], focused_input: &FocusedInput, checklist_input: &str,
+                        checklist_editing: Option<usize>) -> List<'a> { // Render the checklist of the selected to_do
+    let typing = *focused_input == FocusedInput::ChecklistItem;
+    let input_item = || ListItem::new(Spans::from(vec![
+        Span::raw("[ ] "),
+        Span::styled(format!("{}_", checklist_input), Style::default().fg(FOCUS_COLOR)),
+    ]));
+    let mut items: Vec<_> = checklist
+        .iter()
+        .enumerate()
+        .map(|(item, checklist_item)| {
+            if typing && checklist_editing == Some(item) {
+                return input_item();
+            }
+            let style = if checklist_item.checked {
+                Style::default().fg(INACTIVE_COLOR).add_modifier(Modifier::CROSSED_OUT)
+            } else {
+                Style::default().fg(ACTIVE_COLOR)
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw(if checklist_item.checked { "[x] " } else { "[ ] " }),
+                Span::styled(checklist_item.text.clone(), style),
+            ]))
+        })
+        .collect();
+    if typing && checklist_editing.is_none() {
+        items.push(input_item());
+    }
+
+    let checked = checklist.iter().filter(|item| item.checked).count();
+    let focused = typing || *focused_input == FocusedInput::Checklist;
+    let title = format!(
+        "Checklist{} - {}",
+        if checklist.is_empty() { String::new() } else { format!(" {}/{}", checked, checklist.len()) },
+        match focused_input {
+            FocusedInput::Checklist =>
+                "<space> checks, 'n' adds, 'e' edits, 'd' deletes, 'K'/'J' move, <esc> goes back",
+            FocusedInput::ChecklistItem => "<enter> saves, <esc> cancels",
+            _ => "press 'l' to edit",
+        },
+    );
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(ACTIVE_COLOR))
+        .title(title)
+        .border_type(if focused { FOCUS_BORDER } else { DEFAULT_BORDER });
+    if focused {
+        block = block.border_style(Style::default().fg(FOCUS_COLOR));
+    }
+
+    List::new(items)
+        .block(block)
+        .highlight_style(if focused && !typing {
+            Style::default().bg(BG_HIGHLIGHT_COLOR).fg(FOCUS_COLOR).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        })
+}
+
+
+fn render_categories<'a>(counts: &ListCounts) -> List<'a> { // Render the categories with the number of to_dos listed in each
+    let mut items = vec![ListItem::new(Spans::from(vec![Span::raw(format!("All ({})", counts.all_categories))]))];
+
+    for (category, count) in &counts.categories {
+        let count = *count;
+        let style = Style::default().fg(if count == 0 { INACTIVE_COLOR } else { ACTIVE_COLOR });
+        items.push(ListItem::new(Spans::from(vec![Span::styled(
+            format!("{} ({})", category_label(category), count),
+            style,
+        )])));
+    }
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Categories")
+                .border_type(DEFAULT_BORDER),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(BG_HIGHLIGHT_COLOR)
+                .fg(FOCUS_COLOR)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+
+fn render_tags<'a>(counts: &ListCounts) -> Paragraph<'a> { // Render the tag cloud with the number of listed to_dos that have each tag
+    let spans: Vec<Span> = counts.tags
+        .iter()
+        .flat_map(|(tag, count)| {
+            let style = if *count == 0 {
+                Style::default().fg(INACTIVE_COLOR)
+            } else {
+                Style::default().fg(tag_color(tag))
+            };
+            [
+                Span::styled(format!("#{}", tag), style),
+                Span::styled(format!("({}) ", count), Style::default().fg(INACTIVE_COLOR)),
+            ]
+        })
+        .collect();
+
+    Paragraph::new(Spans::from(spans))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Tags")
+                .border_type(DEFAULT_BORDER),
+        )
+}
+
+
+fn render_rename<'a>(category: &str, rename_input: &str) -> Paragraph<'a> { // Render the prompt for a new category name
+    Paragraph::new(rename_input.to_owned())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title(format!("Rename {}", category_label(category)))
+                .border_type(FOCUS_BORDER)
+                .border_style(Style::default().fg(FOCUS_COLOR)),
+        )
+        .style(Style::default().fg(ACTIVE_COLOR))
+}
+
+
+fn render_search<'a>(search: &str, search_error: &Option<String>, focused_input: &FocusedInput)
+    -> Paragraph<'a> { // Render the search prompt, the status line says why a query is invalid
+    let focused = focused_input == &FocusedInput::Search;
+    let title = match search_error {
+        Some(_) => Span::styled("Invalid search", Style::default().fg(Color::Red)),
+        None => Span::raw("Search"),
+    };
+    Paragraph::new(format!("/{}", search))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title(title)
+                .border_type(if focused { FOCUS_BORDER } else { DEFAULT_BORDER })
+                .border_style(Style::default().fg(if focused { FOCUS_COLOR } else { ACTIVE_COLOR })),
+        )
+        .style(Style::default().fg(if focused { ACTIVE_COLOR } else { INACTIVE_COLOR }))
+}
+
+
+fn highlight_matches<'a>(text: &str, positions: &[usize], style: Style)
+    -> Vec<Span<'a>> { // Split text into spans, emphasising the chars at the given positions
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (position, c) in text.chars().enumerate() {
+        let matched = positions.contains(&position);
+        if matched != run_matched && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), match_style(style, run_matched)));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, match_style(style, run_matched)));
+    }
+    spans
+}
+
+
+fn match_style(style: Style, matched: bool) -> Style {
+    if matched {
+        style.fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+    } else {
+        style
+    }
+}
+
+
+fn render_db_error<'a>(error: &serde_json::Error, quarantine: &Option<String>)
+    -> Paragraph<'a> { // Explain why the TODOs can not be shown
+    Paragraph::new(vec![
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::styled(
+            "The database file is corrupt and will not be written to.",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::raw(format!(
+            "{} could not be parsed: {}", db_path().display(), error
+        ))]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::raw(match quarantine {
+            Some(path) => format!("A copy of the bad file was saved to {}", path),
+            None => "No copy of the bad file could be saved.".to_string(),
+        })]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::styled(
+            "Press 'r' to open the recovery view.",
+            Style::default().fg(FOCUS_COLOR),
+        )]),
+    ])
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("TODOs")
+                .border_type(DEFAULT_BORDER),
+        )
+}
+
+
+fn render_recovery<'a>(error: &serde_json::Error, quarantine: &Option<String>)
+    -> (Paragraph<'a>, Paragraph<'a>) { // Render the raw db contents around the parse error
+
+    // Draw help text
+    let help = Paragraph::new(vec![
+        Spans::from(vec![Span::raw(format!("Parse error: {}", error))]),
+        Spans::from(vec![Span::raw(match quarantine {
+            Some(path) => format!("Backup of the bad file: {}", path),
+            None => "No backup of the bad file could be saved.".to_string(),
+        })]),
+        Spans::from(vec![Span::raw(
+            "Fix the file in an editor and press 'r' to reload it, or press 'n' to \
+            start a new empty list once the backup is saved."
+        )]),
+    ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Recovery")
+                .border_type(DEFAULT_BORDER),
+        )
+        .style(Style::default().fg(FOCUS_COLOR));
+
+    // Show the file with line numbers, marking the broken line
+    let contents = String::from_utf8_lossy(&fs::read(db_path()).unwrap_or_default()).into_owned();
+    let lines: Vec<_> = contents
+        .lines()
+        .enumerate()
+        .map(|(number, line)| {
+            let style = if number + 1 == error.line() {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(INACTIVE_COLOR)
+            };
+            Spans::from(vec![
+                Span::styled(format!("{:>5} | ", number + 1), Style::default().fg(INACTIVE_COLOR)),
+                Span::styled(line.to_string(), style),
+            ])
+        })
+        .collect();
+
+    let file = Paragraph::new(lines)
+        .scroll((error.line().saturating_sub(5) as u16, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title(db_path().display().to_string())
+                .border_type(DEFAULT_BORDER),
+        );
+
+    (help, file)
+}
+
+
+fn render_backups<'a>(backups: &[PathBuf]) -> (Paragraph<'a>, List<'a>) { // Render the Backups tab
+
+    // Draw help text
+    let help = Paragraph::new(format!(
+        "Up to {} backups are kept, one is made before every change. Select a backup and \
+        press <enter> to restore it, the current list is backed up first.",
+        backup_count()
+    ))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Help")
+                .border_type(DEFAULT_BORDER),
+        )
+        .style(Style::default().fg(FOCUS_COLOR));
+
+    // Create a list of backups, newest first
+    let items: Vec<_> = backups
+        .iter()
+        .map(|path| {
+            ListItem::new(Spans::from(vec![Span::raw(
+                path.file_name().map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            )]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Backups")
+                .border_type(DEFAULT_BORDER),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(BG_HIGHLIGHT_COLOR)
+                .fg(FOCUS_COLOR)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    (help, list)
+}
+
+
+fn render_trash<'a>(todos: &[Todo]) -> (Paragraph<'a>, List<'a>) { // Render the Trash tab
+
+    // Draw help text
+    let policy = match trash_days() {
+        0 => "Deleted TODOs are kept until they are purged.".to_owned(),
+        days => format!("Deleted TODOs are purged after {} days.", days),
+    };
+    let help = Paragraph::new(format!(
+        "{} Select a TODO and press <enter> to restore it or 'd' to purge it now.",
+        policy
+    ))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Help")
+                .border_type(DEFAULT_BORDER),
+        )
+        .style(Style::default().fg(FOCUS_COLOR));
+
+    // Create a list of trashed to_dos, last deleted first
+    let now = Utc::now();
+    let items: Vec<_> = trashed_indices(todos)
+        .into_iter()
+        .map(|index| {
+            let todo = &todos[index];
+            let deleted_at = todo.deleted_at.map_or_else(String::new, |deleted_at| {
+                deleted_at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+            });
+            let purge = todo.purge_at().map_or_else(String::new, |purge_at| {
+                format!(", purged in {} days", ((purge_at - now).num_hours() + 23) / 24)
+            });
+            ListItem::new(Spans::from(vec![
+                Span::raw(todo.name.clone()),
+                Span::styled(
+                    format!("  deleted {}{}", deleted_at, purge),
+                    Style::default().fg(INACTIVE_COLOR),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Trash")
+                .border_type(DEFAULT_BORDER),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(BG_HIGHLIGHT_COLOR)
+                .fg(FOCUS_COLOR)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    (help, list)
+}
+
+
+fn render_home<'a>(legacy_db: &Option<PathBuf>) -> Paragraph<'a> { // Renders the home page
+    let mut lines = vec![
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::raw("Welcome")]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::raw("to")]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::styled(
+            "todo-CLI",
+            Style::default().fg(FOCUS_COLOR),
+        )]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::raw(
+            "Press 't' to access TODOs, 'a' to add a new TODO \
+            and 'd' to move the currently selected TODO to the trash.")]),
+        Spans::from(vec![Span::raw(
+            "In the TODOs tab press 'x' to mark a TODO as done, 's' to cycle its status, \
+            'e' to edit it, 'c' to hide or show completed TODOs, 'p' to sort them by priority \
+            and '/' to search them.")]),
+        Spans::from(vec![Span::raw(
+            "Press 'o' to cycle the sort order and 'O' to pick a second key for TODOs that tie. \
+            In manual order 'K' and 'J' move a TODO up and down, <home> and <end> to the top and bottom.")]),
+        Spans::from(vec![Span::raw(
+            "Press 'u' to undo the last change and 'Ctrl-r' to redo it.")]),
+        Spans::from(vec![Span::raw(
+            "Saved views from the config are listed on the left of the TODOs tab, \
+            use <left>/<right> or 0-9 to switch between them.")]),
+        Spans::from(vec![Span::raw(
+            "Categories are listed there as well, '[' and ']' pick one to list, 'g' groups the TODOs by category and \
+            <enter> folds a group. 'R' renames a category, or merges it into one that exists.")]),
+        Spans::from(vec![Span::raw(
+            "Below them all tags are shown, press '#' to search for one.")]),
+        Spans::from(vec![Span::raw(
+            "Press 'A' to add a subtask to the selected TODO, '>' and '<' to indent and outdent it, \
+            <enter> to fold its subtasks and 'X' to mark it as done together with them.")]),
+        Spans::from(vec![Span::raw(
+            "Press 'l' to edit the checklist of the selected TODO shown under its details.")]),
+        Spans::from(vec![Span::raw(
+            "TODOs can be blocked by others, give their ids when adding or editing it. \
+            Search for is:ready to list only the TODOs whose blockers are all done.")]),
+        Spans::from(vec![Span::raw(
+            "Give a TODO a repeat rule like \"weekly on mon\" and marking it as done adds the next one, \
+            due on the next day the rule allows.")]),
+        Spans::from(vec![Span::raw(
+            "Press 'b' to restore the list from one of the automatic backups \
+            and 'T' to restore deleted TODOs from the trash.")]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::styled(
+            format!("Your TODOs are stored in {}", db_path().display()),
+            Style::default().fg(INACTIVE_COLOR),
+        )]),
+    ];
+
+    if let Some(legacy_db) = legacy_db { // Offer to move the db of an older version
+        lines.push(Spans::from(vec![Span::raw("")]));
+        lines.push(Spans::from(vec![Span::styled(
+            format!("Found TODOs from an older version in {}, press 'm' to move them here.",
+                    legacy_db.display()),
+            Style::default().fg(Color::Yellow),
+        )]));
+    }
+
+    let home = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(ACTIVE_COLOR))
+                .title("Home")
                 .border_type(DEFAULT_BORDER),
-        )
-        .widths(&[
-            Constraint::Percentage(5),
-            Constraint::Percentage(20),
-            Constraint::Percentage(20),
-            Constraint::Percentage(30),
-            Constraint::Percentage(25),
-        ]);
+        );
+    home
+}
+// ----------------------------------  END OF UI BLOCK FUNCTIONS  ----------------------------------
+
+
+// ----------------------------------     DB-RELATED FUNCTIONS    ----------------------------------
+fn read_db(path: &Path) -> Result<Store, Error> { // Get the store containing all to_dos from the db at path
+    let contents = match fs::read(path) { // Only a missing db is created, other errors leave the file alone
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            write_db(path, &Store::default())?;
+            Vec::new()
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut store = if is_blank(&contents) { // Only a missing or blank db counts as empty, anything else must parse
+        Store::default()
+    } else {
+        parse_store(&contents)?
+    };
+
+    let now = Utc::now(); // Drop expired trash, the next write makes that permanent
+    store.todos.retain(|todo| todo.purge_at().is_none_or(|purge_at| purge_at > now));
+    Ok(store)
+}
+
+
+fn parse_store(contents: &[u8]) -> Result<Store, serde_json::Error> { // Also reads the old format, bad UTF-8 is an error
+    if contents.trim_ascii_start().starts_with(b"[") { // Older versions saved a bare list of to_dos
+        Ok(Store { next_id: 0, todos: serde_json::from_slice(contents)? })
+    } else {
+        serde_json::from_slice(contents)
+    }
+}
+
+
+fn is_blank(contents: &[u8]) -> bool {
+    contents.iter().all(u8::is_ascii_whitespace)
+}
+
+
+fn write_db(path: &Path, store: &Store) -> Result<(), Error> { // Replace the contents of the db at path with the given store
+    let contents = serde_json::to_vec(store)?;
+    if path.exists() {
+        backup_db(path)?;
+    }
+    write_file(path, &contents)
+}
+
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), Error> { // Replace a file next to the db as a whole
+    // Write a temporary file first so a crash never leaves a half-written file behind
+    let mut temp_path: OsString = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    #[cfg(unix)]
+    fs::File::open(db_dir(path))?.sync_all()?; // Make the rename itself durable
+    Ok(())
+}
+
+
+fn db_dir(path: &Path) -> PathBuf { // Directory the db at path and its backups live in
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+
+fn db_stamp() -> Option<(SystemTime, u64)> { // Identifies the current version of the db file
+    let metadata = fs::metadata(db_path()).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+
+#[cfg(target_os = "linux")]
+fn watch_db<I: Send + 'static>(tx: mpsc::Sender<Event<I>>) { // Send Event::Changed when the db is replaced
+    use inotify::{Inotify, WatchMask};
+
+    let file_name = match db_path().file_name() {
+        Some(name) => name.to_owned(),
+        None => return,
+    };
+    let mut inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(_) => return, // Event::Tick still polls the file
+    };
+    let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE | WatchMask::DELETE;
+    if inotify.add_watch(db_dir(db_path()), mask).is_err() { // Watch the directory as writes rename over the file
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(events) = inotify.read_events_blocking(&mut buffer) {
+            let changed = events.into_iter().any(|event| event.name == Some(file_name.as_os_str()));
+            if changed && tx.send(Event::Changed).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+
+#[cfg(not(target_os = "linux"))]
+fn watch_db<I: Send + 'static>(_tx: mpsc::Sender<Event<I>>) {} // Event::Tick polls the file instead
+
+
+fn db_sibling(db: &Path, suffix: &str) -> PathBuf { // Path of a file next to the db, named after it
+    let mut path: OsString = db.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+
+fn trash_days() -> i64 { // Days to keep trashed to_dos, 0 keeps them forever
+    config().trash_days.unwrap_or(TRASH_DAYS)
+}
+
+
+fn backup_count() -> usize { // Number of backups to keep, 0 disables them
+    env::var(BACKUP_COUNT_VAR)
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .or(config().backups)
+        .unwrap_or(BACKUP_COUNT)
+}
+
+
+fn backup_db(path: &Path) -> Result<(), Error> { // Copy the db at path aside and drop the oldest backups
+    let count = backup_count();
+    if count == 0 {
+        return Ok(());
+    }
+
+    fs::copy(path, db_sibling(path, &format!(".bak-{}", Utc::now().format("%Y%m%d-%H%M%S%.3f"))))?;
+    for old_backup in list_backups(path)?.iter().skip(count) {
+        fs::remove_file(old_backup)?;
+    }
+    Ok(())
+}
+
+
+fn list_backups(path: &Path) -> Result<Vec<PathBuf>, Error> { // Get all backups of the db at path, newest first
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.bak-", name.to_string_lossy()),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(db_dir(path))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect();
+
+    backups.sort(); // Timestamps sort chronologically
+    backups.reverse();
+    Ok(backups)
+}
+
+
+fn restore_backup(backup: &Path) -> Result<Store, Error> { // Replace the db with a backup
+    let parsed = parse_store(&fs::read(backup)?)?;
+    write_db(db_path(), &parsed)?;
+    write_history(&History::default())?; // The changes were made to other to_dos
+    Ok(parsed)
+}
+
+
+fn quarantine_db(path: &Path) -> Result<String, Error> { // Keep a copy of a db at path that can not be parsed
+    let quarantine = db_sibling(path, &format!(".corrupt-{}", Utc::now().format("%Y%m%d-%H%M%S")));
+    fs::copy(path, &quarantine)?;
+    Ok(quarantine.display().to_string())
+}
+
+
+fn find_legacy_db() -> Option<PathBuf> { // Get the db of an older version if it can be moved
+    let legacy_db = PathBuf::from(LEGACY_DB_PATH);
+    if !legacy_db.is_file() || fs::canonicalize(&legacy_db).ok() == fs::canonicalize(db_path()).ok() {
+        return None;
+    }
+
+    // Only offer the move while the new db holds no to_dos, so nothing gets overwritten
+    match fs::read(db_path()) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Some(legacy_db),
+        Err(_) => None,
+        Ok(contents) => match parse_store(&contents) {
+            Ok(store) if store.todos.is_empty() => Some(legacy_db),
+            _ if is_blank(&contents) => Some(legacy_db),
+            _ => None,
+        },
+    }
+}
+
+
+fn migrate_legacy_db(legacy_db: &Path) -> Result<(), Error> { // Move an old db to the new location
+    let parsed = parse_store(&fs::read(legacy_db)?)?;
+    write_db(db_path(), &parsed)?;
+    write_history(&History::default())?; // The changes were made to other to_dos
+
+    let mut migrated: OsString = legacy_db.as_os_str().to_owned();
+    migrated.push(".migrated");
+    fs::rename(legacy_db, migrated)?;
+    Ok(())
+}
+
+
+fn add_todo_from_input(store: &mut Store, input_states: &InputStates)
+    -> Result<Change, Error> { // Add to_do to the store
+    let due = parse_due(&input_states.due, Local::now())?;
+    let recurrence = parse_recurrence(&input_states.repeat)?;
+    let tags = input_states.all_tags();
+    if let Some(parent) = input_states.parent.filter(|&parent| !store.todos.iter().any(|todo| todo.id == parent)) {
+        return Err(Error::TodoNotFound(parent));
+    }
+    let blocked_by = parse_ids(&input_states.blocked_by)?;
+    if let Some(&blocker) = blocked_by.iter().find(|&&blocker| !store.todos.iter().any(|todo| todo.id == blocker)) {
+        return Err(Error::TodoNotFound(blocker)); // A new to_do can not be part of a cycle yet
+    }
+
+    let default_todo = Todo {
+        id: store.allocate_id(),
+        uuid: Some(new_uuid()),
+        name: input_states.name.to_owned(),
+        category: input_states.category.to_uppercase().to_owned(),
+        tags,
+        text: input_states.text.to_owned(),
+        created_at: Utc::now(),
+        status: TodoStatus::Open,
+        completed_at: None,
+        priority: input_states.priority,
+        due,
+        updated_at: None,
+        deleted_at: None,
+        position: store.todos.iter().map(|todo| todo.position + 1).max().unwrap_or(0), // Add at the end
+        parent: input_states.parent,
+        blocked_by,
+        checklist: Vec::new(),
+        recurrence,
+    };
+
+    store.todos.push(default_todo.clone());
+
+    Ok(Change::Added { index: store.todos.len() - 1, todo: default_todo })
+}
+
+
+fn update_todo_from_input(store: &mut Store, id: usize, input_states: &InputStates)
+    -> Result<Change, Error> { // Overwrite the editable fields of an existing to_do
+    let due = parse_due(&input_states.due, Local::now())?;
+    let recurrence = parse_recurrence(&input_states.repeat)?;
+    let tags = input_states.all_tags();
+    let blocked_by = parse_ids(&input_states.blocked_by)?;
+    check_blockers(&store.todos, id, &blocked_by)?;
+
+    let todo = store.live_todo_mut(id)?;
+    let before = todo.clone();
+    todo.name = input_states.name.to_owned();
+    todo.category = input_states.category.to_uppercase();
+    todo.tags = tags;
+    todo.text = input_states.text.to_owned();
+    todo.priority = input_states.priority;
+    todo.due = due;
+    todo.blocked_by = blocked_by;
+    todo.recurrence = recurrence;
+    todo.updated_at = Some(Utc::now());
+
+    Ok(Change::Edited { before, after: todo.clone() })
+}
+
+
+fn trash_todo_with_id(store: &mut Store, id: usize)
+    -> Result<Change, Error> { // Move the to_do with the given id to the trash
+    let todo = store.live_todo_mut(id)?;
+    let before = todo.clone();
+    todo.deleted_at = Some(Utc::now());
+    Ok(Change::Trashed { before, after: todo.clone() })
+}
+
+
+fn restore_todo_with_id(store: &mut Store, id: usize)
+    -> Result<Change, Error> { // Move the to_do with the given id out of the trash
+    let todo = store.todos
+        .iter_mut()
+        .find(|todo| todo.id == id && todo.is_trashed())
+        .ok_or(Error::TodoNotFound(id))?;
+    let before = todo.clone();
+    todo.deleted_at = None;
+    Ok(Change::Restored { before, after: todo.clone() })
+}
+
+
+fn remove_todo_with_id(store: &mut Store, id: usize)
+    -> Result<Change, Error> { // Delete the to_do with the given id for good
+    let index = store.todos.iter().position(|todo| todo.id == id).ok_or(Error::TodoNotFound(id))?;
+    Ok(Change::Removed { index, todo: store.todos.remove(index) })
+}
+
+
+fn change_status_with_id(store: &mut Store, id: usize, status: TodoStatus)
+    -> Result<Change, Error> { // Set the status of the to_do with the given id, finishing a recurring one adds the next
+    let todo = store.live_todo_mut(id)?;
+    let before = todo.clone();
+    todo.set_status(status);
+    let after = todo.clone();
+    let change = Change::StatusChanged { before: before.clone(), after: after.clone() };
+    if status != TodoStatus::Done || before.is_completed() {
+        return Ok(change);
+    }
+    let Some(recurrence) = &after.recurrence else {
+        return Ok(change);
+    };
+
+    let now = Utc::now();
+    let due = recurrence.next_due(after.due, after.completed_at.unwrap_or(now));
+    if store.todos.iter().any(|todo| { // It is there already when a to_do is reopened and done again
+        !todo.is_completed() && !todo.is_trashed() && todo.id != id
+            && todo.name == after.name && todo.recurrence == after.recurrence && todo.due == due
+    }) {
+        return Ok(change);
+    }
+    let next = Todo {
+        id: store.allocate_id(),
+        uuid: Some(new_uuid()),
+        created_at: now,
+        status: TodoStatus::Open,
+        completed_at: None,
+        due,
+        updated_at: None,
+        position: store.todos.iter().map(|todo| todo.position + 1).max().unwrap_or(0),
+        checklist: after.checklist.iter().map(|item| ChecklistItem { checked: false, ..item.clone() }).collect(),
+        ..after.clone()
+    };
+    store.todos.push(next.clone());
+    Ok(Change::Batch {
+        description: format!("marking '{}' as done and adding the next one", after.name),
+        changes: vec![change, Change::Added { index: store.todos.len() - 1, todo: next }],
+    })
+}
+
+
+fn change_checklist_with_id(store: &mut Store, id: usize, change: impl FnOnce(&mut Vec<ChecklistItem>))
+    -> Result<Change, Error> { // Edit the checklist of the to_do with the given id
+    let todo = store.live_todo_mut(id)?;
+    let before = todo.clone();
+    change(&mut todo.checklist);
+    todo.updated_at = Some(Utc::now());
+    Ok(Change::Edited { before, after: todo.clone() })
+}
+
+
+fn set_parent_with_id(store: &mut Store, id: usize, parent: Option<usize>)
+    -> Result<Change, Error> { // Make the to_do with the given id a subtask of another one, or of none
+    let todo = store.live_todo_mut(id)?;
+    let before = todo.clone();
+    todo.parent = parent;
+    Ok(Change::Edited { before, after: todo.clone() })
+}
+
+
+fn complete_with_subtasks(store: &mut Store, id: usize)
+    -> Result<Change, Error> { // Mark the to_do with the given id and all its subtasks as done
+    let name = store.todos.iter().find(|todo| todo.id == id).ok_or(Error::TodoNotFound(id))?.name.clone();
+    let mut ids = vec![id];
+    let mut seen = std::collections::HashSet::new(); // To_dos may be each other's parents
+    let mut changes = Vec::new();
+    while let Some(id) = ids.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        ids.extend(store.todos.iter().filter(|todo| todo.parent == Some(id) && !todo.is_trashed()).map(|todo| todo.id));
+        if store.todos.iter().any(|todo| todo.id == id && !todo.is_completed()) { // Cancelled ones stay cancelled
+            changes.push(change_status_with_id(store, id, TodoStatus::Done)?);
+        }
+    }
+    Ok(Change::Batch { description: format!("marking '{}' and its subtasks as done", name), changes })
+}
+
+
+fn read_history() -> History { // Get the undo history, empty if there is none or it can not be read
+    fs::read_to_string(db_sibling(db_path(), ".history"))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+
+fn write_history(history: &History) -> Result<(), Error> { // A torn write would lose the whole history
+    write_file(&db_sibling(db_path(), ".history"), &serde_json::to_vec(history)?)
+}
+
+
+fn record_change(change: Change) -> Result<(), Error> { // Add a change to the undo history
+    let mut history = read_history();
+    history.record(change);
+    write_history(&history)
+}
+
+
+fn trashed_indices(todos: &[Todo]) -> Vec<usize> { // Indices of trashed to_dos, last deleted first
+    let mut indices: Vec<usize> = (0..todos.len()).filter(|&index| todos[index].is_trashed()).collect();
+    indices.sort_by_key(|&index| std::cmp::Reverse(todos[index].deleted_at));
+    indices
+}
+
+
+fn new_uuid() -> String { // Random version 4 UUID
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+
+fn view_filter(list_options: &ListOptions, now: DateTime<Local>)
+    -> Option<Vec<Filter>> { // Terms of the selected view, None for an invalid view which lists nothing
+    match list_options.view().map(|view| view.parse(now)) {
+        Some(Ok(filter)) => Some(filter),
+        Some(Err(_)) => None, // render_views() tells why
+        None => Some(Vec::new()),
+    }
+}
+
+
+fn is_listed(todo: &Todo, todos: &[Todo], ids: &HashMap<usize, usize>, list_options: &ListOptions,
+             view_filter: &[Filter]) -> bool { // Whether the list options let a to_do through
+    !todo.is_trashed()
+        && (list_options.show_completed || !todo.is_completed())
+        && list_options.filter.iter().all(|filter| filter.matches(todo, todos, ids))
+        && view_filter.iter().all(|filter| filter.matches(todo, todos, ids))
+        && list_options.category.as_ref().is_none_or(|category| todo.category.to_uppercase() == *category)
+}
+
+
+fn visible_indices(todos: &[Todo], ids: &HashMap<usize, usize>, list_options: &ListOptions)
+    -> Vec<usize> { // Indices of listed to_dos
+    let Some(view_filter) = view_filter(list_options, Local::now()) else {
+        return Vec::new();
+    };
+    let mut indices: Vec<usize> = (0..todos.len())
+        .filter(|&index| is_listed(&todos[index], todos, ids, list_options, &view_filter))
+        .collect();
+
+    indices.sort_by(|&a, &b| { // Ties fall back to the manual order, then to the order of the db
+        list_options.sort.iter().chain([SortKey::Manual].iter()).fold(std::cmp::Ordering::Equal, |ordering, key| {
+            ordering.then_with(|| key.compare(&todos[a], &todos[b]))
+        })
+    });
+    indices
+}
+
+
+fn list_counts(todos: &[Todo], list_options: &ListOptions)
+    -> ListCounts { // Count the listed to_dos of each view, category and tag, without sorting them
+    let now = Local::now();
+    let ids = index_by_id(todos);
+    let listed = |list_options: &ListOptions| -> Vec<&Todo> {
+        match view_filter(list_options, now) {
+            Some(view_filter) => todos
+                .iter()
+                .filter(|todo| is_listed(todo, todos, &ids, list_options, &view_filter))
+                .collect(),
+            None => Vec::new(),
+        }
+    };
+
+    let all_options = ListOptions {
+        view: 0, search: String::new(), filter: Vec::new(), category: None, ..list_options.clone()
+    };
+    let mut views = vec![Ok(listed(&all_options).len())];
+    for (index, view) in config().views.iter().enumerate() {
+        views.push(match view.parse(now) {
+            Ok(_) => Ok(listed(&ListOptions { view: index + 1, ..all_options.clone() }).len()),
+            Err(error) => Err(error.to_string()),
+        });
+    }
+
+    let in_any_category = listed(&ListOptions { category: None, ..list_options.clone() });
+    let mut by_category: HashMap<String, usize> = HashMap::new();
+    for todo in &in_any_category {
+        *by_category.entry(todo.category.to_uppercase()).or_default() += 1;
+    }
+    let mut by_tag: HashMap<&str, usize> = HashMap::new();
+    for todo in listed(list_options) {
+        for tag in &todo.tags {
+            *by_tag.entry(tag).or_default() += 1;
+        }
+    }
+
+    ListCounts {
+        views,
+        categories: categories(todos)
+            .into_iter()
+            .map(|category| {
+                let count = by_category.get(&category).copied().unwrap_or(0);
+                (category, count)
+            })
+            .collect(),
+        all_categories: in_any_category.len(),
+        tags: tags(todos)
+            .into_iter()
+            .map(|tag| {
+                let count = by_tag.get(tag.as_str()).copied().unwrap_or(0);
+                (tag, count)
+            })
+            .collect(),
+    }
+}
+
+
+fn list_rows(todos: &[Todo], list_options: &ListOptions, collapsed: &[String], folded: &[usize])
+    -> Vec<ListRow> { // Lines of the TODOs list, under a header per category while grouping
+    let ids = index_by_id(todos);
+    let visible = visible_indices(todos, &ids, list_options);
+    let progress = subtask_progress(todos);
+    if !list_options.group {
+        return tree_rows(todos, &ids, &visible, folded, &progress);
+    }
+
+    let mut rows = Vec::new();
+    for category in categories(todos) { // Each group keeps the sort order of the list
+        let members: Vec<usize> = visible
+            .iter()
+            .copied()
+            .filter(|&index| todos[index].category.to_uppercase() == category)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let collapsed = collapsed.contains(&category);
+        rows.push(ListRow::Group { category, count: members.len(), collapsed });
+        if !collapsed {
+            rows.extend(tree_rows(todos, &ids, &members, folded, &progress));
+        }
+    }
+    rows
+}
+
+
+fn tree_rows(todos: &[Todo], ids: &HashMap<usize, usize>, members: &[usize], folded: &[usize],
+             progress: &HashMap<usize, (usize, usize)>) -> Vec<ListRow> { // Lines for the given to_dos in their order, each followed by its listed subtasks
+    let listed: std::collections::HashSet<usize> = members.iter().map(|&index| todos[index].id).collect();
+    let is_root = |index: &&usize| todos[**index].parent.is_none_or(|parent| !listed.contains(&parent));
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new(); // Listed subtasks by the id of their parent
+    for &index in members {
+        if let Some(parent) = todos[index].parent {
+            children.entry(parent).or_default().push(index);
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    let mut rows = Vec::new();
+
+    // Subtasks of hidden to_dos show up on the top level, as do to_dos that are each other's parents
+    for &root in members.iter().filter(is_root).chain(members) {
+        let mut stack = vec![(root, 0, true)];
+        while let Some((index, depth, shown)) = stack.pop() {
+            if !seen.insert(index) {
+                continue;
+            }
+            let id = todos[index].id;
+            let folded = folded.contains(&id);
+            if shown {
+                let subtasks = progress.get(&id).copied().unwrap_or_default();
+                let blocked = is_blocked(todos, ids, &todos[index]);
+                rows.push(ListRow::Todo { index, depth, folded, subtasks, blocked });
+            }
+            for &child in children.get(&id).into_iter().flatten().rev() {
+                stack.push((child, depth + 1, shown && !folded));
+            }
+        }
+    }
+    rows
+}
+
+
+fn subtask_progress(todos: &[Todo])
+    -> HashMap<usize, (usize, usize)> { // Completed and all subtasks outside the trash, by the id of their parent
+    let mut progress = HashMap::new();
+    for todo in todos.iter().filter(|todo| !todo.is_trashed()) {
+        if let Some(parent) = todo.parent {
+            let (done, all): &mut (usize, usize) = progress.entry(parent).or_default();
+            *done += usize::from(todo.is_completed());
+            *all += 1;
+        }
+    }
+    progress
+}
+
+
+fn index_by_id(todos: &[Todo]) -> HashMap<usize, usize> { // Index of the to_do with each id, the first one of a shared id
+    let mut ids = HashMap::new();
+    for (index, todo) in todos.iter().enumerate() {
+        ids.entry(todo.id).or_insert(index);
+    }
+    ids
+}
+
+
+fn is_blocked(todos: &[Todo], ids: &HashMap<usize, usize>, todo: &Todo)
+    -> bool { // Waits for a to_do outside the trash that is not done, ids comes from index_by_id()
+    todo.blocked_by
+        .iter()
+        .filter_map(|blocker| ids.get(blocker))
+        .any(|&index| !todos[index].is_trashed() && !todos[index].is_completed())
+}
+
+
+fn dependents(todos: &[Todo], id: usize) -> Vec<usize> { // Ids of the to_dos outside the trash that wait for this one
+    todos.iter().filter(|todo| todo.blocked_by.contains(&id) && !todo.is_trashed()).map(|todo| todo.id).collect()
+}
+
+
+fn check_blockers(todos: &[Todo], id: usize, blocked_by: &[usize])
+    -> Result<(), Error> { // Blockers have to exist and must not wait for the to_do, not even through others
+    for &blocker in blocked_by {
+        if !todos.iter().any(|todo| todo.id == blocker) {
+            return Err(Error::TodoNotFound(blocker));
+        }
+        let mut waiting = vec![blocker];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(current) = waiting.pop() {
+            if current == id {
+                return Err(Error::DependencyCycle(id, blocker));
+            }
+            if seen.insert(current) {
+                waiting.extend(todos.iter().filter(|todo| todo.id == current).flat_map(|todo| todo.blocked_by.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+
+fn parse_ids(input: &str) -> Result<Vec<usize>, Error> { // Ids typed as "3, 12 #7"
+    let mut ids = input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|id| !id.is_empty())
+        .map(|id| id.trim_start_matches('#').parse().map_err(|_| Error::ParseIdError(id.to_owned())))
+        .collect::<Result<Vec<usize>, Error>>()?;
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+
+fn format_ids(ids: &[usize]) -> String { // "3, 12"
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+
+fn categories(todos: &[Todo]) -> Vec<String> { // Uppercased categories of the to_dos outside the trash, "" last
+    let mut categories: Vec<String> = todos
+        .iter()
+        .filter(|todo| !todo.is_trashed())
+        .map(|todo| todo.category.to_uppercase())
+        .collect();
+    categories.sort_by_key(|category| (category.is_empty(), category.clone()));
+    categories.dedup();
+    categories
+}
+
+
+fn category_label(category: &str) -> &str { // How a category is shown, to_dos may have none
+    if category.is_empty() { "no category" } else { category }
+}
+
+
+fn tags(todos: &[Todo]) -> Vec<String> { // Tags of the to_dos outside the trash, in alphabetical order
+    let mut tags: Vec<String> = todos
+        .iter()
+        .filter(|todo| !todo.is_trashed())
+        .flat_map(|todo| todo.tags.iter().cloned())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
 
-    (list, todo_detail)
+
+fn tag_of(word: &str) -> Option<String> { // "#Work," is the tag "work", "#12" is no tag
+    let tag = word.strip_prefix('#')?.trim_end_matches(|c: char| ",.;:!?".contains(c));
+    let valid = tag.starts_with(char::is_alphabetic)
+        && tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'));
+    valid.then(|| tag.to_lowercase())
 }
 
 
-fn render_home<'a>() -> Paragraph<'a> { // Renders the home page
-    let home = Paragraph::new(vec![
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::raw("Welcome")]),
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::raw("to")]),
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::styled(
-            "todo-CLI",
-            Style::default().fg(FOCUS_COLOR),
-        )]),
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::raw(
-            "Press 't' to access TODOs, 'a' to add a new TODO \
-            and 'd' to delete the currently selected TODO.")]),
-    ])
-        .alignment(Alignment::Center)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(ACTIVE_COLOR))
-                .title("Home")
-                .border_type(DEFAULT_BORDER),
-        );
-    home
+fn tags_in(text: &str) -> impl Iterator<Item = String> + '_ { // Tags of the #tag words in a text
+    text.split_whitespace().filter_map(tag_of)
+}
+
+
+fn parse_tags(input: &str) -> Vec<String> { // Tags typed as "work, #home errands"
+    input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|tag| tag.trim_start_matches('#').to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+
+fn format_tags(tags: &[String]) -> String { // "#home #work"
+    tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" ")
+}
+
+
+fn rename_category(store: &mut Store, category: &str, new_name: &str)
+    -> Result<Change, Error> { // Move all to_dos of a category to another one, which merges them if it exists
+    let (category, new_name) = (category.to_uppercase(), new_name.trim().to_uppercase());
+    let now = Utc::now();
+    let changes: Vec<Change> = store.todos
+        .iter_mut()
+        .filter(|todo| todo.category.to_uppercase() == category)
+        .map(|todo| {
+            let before = todo.clone();
+            todo.category = new_name.clone();
+            todo.updated_at = Some(now);
+            Change::Edited { before, after: todo.clone() }
+        })
+        .collect();
+    if changes.is_empty() {
+        return Err(Error::CategoryNotFound(category_label(&category).to_owned()));
+    }
+
+    let description = format!(
+        "moving {} TODO{} from {} to {}",
+        changes.len(), if changes.len() == 1 { "" } else { "s" }, category_label(&category), category_label(&new_name)
+    );
+    Ok(Change::Batch { description, changes })
+}
+// ---------------------------------- END OF DB-RELATED FUNCTIONS ----------------------------------
+
+
+// ----------------------------------       CONFIG FUNCTIONS      ----------------------------------
+fn db_path() -> &'static Path { // Location of the db, see resolve_db_path()
+    DB_PATH.get_or_init(|| resolve_db_path(None))
+}
+
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+
+fn resolve_db_path(flag: Option<PathBuf>) -> PathBuf { // Pick the db from flag, env, config or XDG
+    flag
+        .or_else(|| env::var_os(DB_PATH_VAR).filter(|path| !path.is_empty()).map(PathBuf::from))
+        .or_else(|| config().db_path.clone())
+        .map(|path| expand_home(&path))
+        .unwrap_or_else(|| xdg_dir("XDG_DATA_HOME", ".local/share").join(APP_DIR).join(DB_FILE_NAME))
+}
+
+
+fn read_config() -> Result<Config, Error> { // Get the config file contents, defaults if it is missing
+    let path = xdg_dir("XDG_CONFIG_HOME", ".config").join(APP_DIR).join(CONFIG_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|error| Error::ParseConfigError(path, error)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+
+fn tag_color(tag: &str) -> Color { // Color of a tag as set in the theme
+    config().theme.tags
+        .iter()
+        .find(|(name, _)| name.trim_start_matches('#').to_lowercase() == tag)
+        .map_or(TAG_COLOR, |(_, color)| color.0)
+}
+
+
+fn parse_color(name: &str) -> Option<Color> { // Understand "light-blue", "dark gray" and "#ff8800"
+    if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    Some(match name.to_lowercase().replace([' ', '_'], "-").as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "dark-gray" | "dark-grey" => Color::DarkGray,
+        "light-red" => Color::LightRed,
+        "light-green" => Color::LightGreen,
+        "light-yellow" => Color::LightYellow,
+        "light-blue" => Color::LightBlue,
+        "light-magenta" => Color::LightMagenta,
+        "light-cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+
+fn read_ui_state() -> UiState { // Get the remembered interface settings, defaults if there are none
+    fs::read_to_string(ui_state_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+
+fn write_ui_state(state: &UiState) -> Result<(), Error> {
+    let path = ui_state_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(state)?)?;
+    Ok(())
+}
+
+
+fn ui_state_path() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state").join(APP_DIR).join(STATE_FILE_NAME)
+}
+
+
+fn xdg_dir(variable: &str, fallback: &str) -> PathBuf { // Base directory as per the XDG spec
+    match env::var_os(variable).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home_dir().join(fallback),
+    }
+}
+
+
+fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."))
+}
+
+
+fn expand_home(path: &Path) -> PathBuf { // Replace a leading "~" with the home directory
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+// ----------------------------------   END OF CONFIG FUNCTIONS   ----------------------------------
+
+
+// ----------------------------------        DATE FUNCTIONS       ----------------------------------
+fn parse_due(input: &str, now: DateTime<Local>)
+    -> Result<Option<DateTime<Utc>>, Error> { // Understand ISO dates and simple phrases
+    let phrase = input.trim().to_lowercase();
+    if phrase.is_empty() {
+        return Ok(None);
+    }
+    let unknown = || Error::ParseDueError(input.trim().to_string());
+    let today = now.date_naive();
+
+    let due = match phrase.as_str() {
+        "now" => Some(now),
+        "eod" | "today" | "tonight" => end_of_day(today),
+        "tomorrow" | "tmr" => end_of_day(today + Duration::days(1)),
+        _ => {
+            if let Some(amount) = phrase.strip_prefix("in ") { // "in 3 days", "in 2h"
+                parse_offset(amount).map(|offset| now + offset)
+            } else if let Ok(weekday) = phrase.trim_start_matches("next ").parse::<Weekday>() {
+                let days_ahead = (weekday.num_days_from_monday() + 7
+                    - today.weekday().num_days_from_monday()) % 7;
+                end_of_day(today + Duration::days(if days_ahead == 0 { 7 } else { days_ahead.into() }))
+            } else if let Ok(date) = NaiveDate::parse_from_str(&phrase, "%Y-%m-%d") {
+                end_of_day(date)
+            } else if let Ok(date_time) = NaiveDateTime::parse_from_str(&phrase, "%Y-%m-%d %H:%M")
+                .or_else(|_| NaiveDateTime::parse_from_str(&phrase, "%Y-%m-%dt%H:%M")) {
+                Local.from_local_datetime(&date_time).earliest()
+            } else if let Ok(date_time) = DateTime::parse_from_rfc3339(input.trim()) {
+                Some(date_time.with_timezone(&Local))
+            } else {
+                None
+            }
+        }
+    };
+
+    due.map(|due| Some(due.with_timezone(&Utc))).ok_or_else(unknown)
+}
+
+
+fn parse_offset(amount: &str) -> Option<Duration> { // Turn "3 days" or "2h" into a duration
+    let amount = amount.trim();
+    let split = amount.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = amount.split_at(split);
+    let number: i64 = number.parse().ok()?;
+
+    match unit.trim() {
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(number)),
+        "h" | "hour" | "hours" => Some(Duration::hours(number)),
+        "d" | "day" | "days" => Some(Duration::days(number)),
+        "w" | "week" | "weeks" => Some(Duration::weeks(number)),
+        _ => None,
+    }
+}
+
+
+fn parse_recurrence(input: &str)
+    -> Result<Option<Recurrence>, Error> { // Understand rules like "weekly on mon, fri" or "every 3 days after completion"
+    let phrase = input.trim().to_lowercase();
+    let words: Vec<&str> = phrase
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty() && !matches!(*word, "on" | "the" | "and"))
+        .collect();
+    if words.is_empty() {
+        return Ok(None);
+    }
+    let weekdays = |words: &[&str]| -> Option<Vec<Weekday>> { // "mon", "fridays"
+        let mut weekdays = words
+            .iter()
+            .map(|word| word.trim_end_matches('s').parse::<Weekday>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+        weekdays.dedup();
+        (!weekdays.is_empty()).then_some(weekdays)
+    };
+    let after_completion = |words: &[&str]| matches!(words, [] | ["after", "completion" | "completing" | "done"]);
+
+    let recurrence = match words.as_slice() {
+        ["daily"] | ["every", "day"] => Some(Recurrence::Daily),
+        ["weekdays"] | ["every", "weekday"] => Some(Recurrence::Weekly {
+            weekdays: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        }),
+        ["weekly", days @ ..] | ["every", "week", days @ ..] => weekdays(days).map(|weekdays| Recurrence::Weekly { weekdays }),
+        ["monthly", day] | ["every", "month", day] => day
+            .trim_end_matches(char::is_alphabetic) // "15th"
+            .parse()
+            .ok()
+            .filter(|day| (1..=31).contains(day))
+            .map(|day| Recurrence::Monthly { day }),
+        ["every", "day", rest @ ..] if after_completion(rest) => Some(Recurrence::AfterCompletion { days: 1 }),
+        ["every", days, "day" | "days", rest @ ..] if after_completion(rest) => days
+            .parse()
+            .ok()
+            .filter(|&days| days > 0)
+            .map(|days| Recurrence::AfterCompletion { days }),
+        ["every", days @ ..] => weekdays(days).map(|weekdays| Recurrence::Weekly { weekdays }),
+        _ => None,
+    };
+    recurrence.map(Some).ok_or_else(|| Error::ParseRecurrenceError(input.trim().to_owned()))
+}
+
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+
+fn join_words(words: &[&str]) -> String { // "a, b and c"
+    match words {
+        [] => String::new(),
+        [word] => word.to_string(),
+        [init @ .., last] => format!("{} and {}", init.join(", "), last),
+    }
+}
+
+
+fn ordinal(number: u32) -> String { // "1st", "12th", "22nd"
+    let suffix = match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", number, suffix)
+}
+
+
+fn day_of_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> { // The day, or the last one of shorter months
+    (1..=day).rev().find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+
+fn end_of_day(date: NaiveDate) -> Option<DateTime<Local>> { // Last second of a local day
+    Local.from_local_datetime(&date.and_hms_opt(23, 59, 59)?).earliest()
+}
+
+
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> { // Local midnight at the start of a day
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()
+}
+
+
+fn date_range(phrase: &str, today: NaiveDate)
+    -> Option<(NaiveDate, NaiveDate)> { // First day and the day after "last-week", "2024-03" and so on
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday().into());
+    let month_start = today.with_day(1)?;
+    let day = |offset: i64| today + Duration::days(offset);
+
+    Some(match phrase {
+        "today" => (today, day(1)),
+        "yesterday" => (day(-1), today),
+        "tomorrow" => (day(1), day(2)),
+        "this-week" => (week_start, week_start + Duration::days(7)),
+        "last-week" => (week_start - Duration::days(7), week_start),
+        "next-week" => (week_start + Duration::days(7), week_start + Duration::days(14)),
+        "this-month" => (month_start, add_months(month_start, 1)?),
+        "last-month" => (add_months(month_start, -1)?, month_start),
+        "next-month" => (add_months(month_start, 1)?, add_months(month_start, 2)?),
+        _ => match NaiveDate::parse_from_str(phrase, "%Y-%m-%d") {
+            Ok(date) => (date, date + Duration::days(1)),
+            Err(_) => {
+                let month = NaiveDate::parse_from_str(&format!("{}-01", phrase), "%Y-%m-%d").ok()?;
+                (month, add_months(month, 1)?)
+            }
+        },
+    })
+}
+
+
+fn add_months(month_start: NaiveDate, months: i32) -> Option<NaiveDate> { // First day of another month
+    let months = month_start.year() * 12 + month_start.month0() as i32 + months;
+    NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+}
+
+
+fn format_countdown(due: DateTime<Utc>, now: DateTime<Utc>) -> String { // "due in 2h", "3 days overdue"
+    let delta = due - now;
+    let span = if delta < Duration::zero() { -delta } else { delta };
+    let span = Duration::minutes((span.num_seconds() + 30) / 60); // Round to whole minutes
+
+    let amount = if span.num_days() >= 1 {
+        format!("{} day{}", span.num_days(), if span.num_days() == 1 { "" } else { "s" })
+    } else if span.num_hours() >= 1 {
+        format!("{}h", span.num_hours())
+    } else {
+        format!("{}m", span.num_minutes())
+    };
+
+    if delta < Duration::zero() {
+        format!("{} overdue", amount)
+    } else {
+        format!("due in {}", amount)
+    }
+}
+// ----------------------------------    END OF DATE FUNCTIONS    ----------------------------------
+
+
+// ----------------------------------       SEARCH FUNCTIONS      ----------------------------------
+fn parse_filter(query: &str, now: DateTime<Local>)
+    -> Result<Vec<Filter>, Error> { // Understand queries like "category:WORK -status:done deploy"
+    let chars: Vec<char> = query.chars().collect();
+    let mut position = 0;
+    let mut filters = Vec::new();
+
+    while position < chars.len() {
+        if chars[position].is_whitespace() {
+            position += 1;
+            continue;
+        }
+        let negated = chars[position] == '-';
+        if negated {
+            position += 1;
+        }
+
+        let key_start = position;
+        let mut key = String::new();
+        while let Some(&c) = chars.get(position).filter(|c| !c.is_whitespace() && **c != ':' && **c != '"') {
+            key.push(c);
+            position += 1;
+        }
+
+        let filter = match chars.get(position) {
+            Some(':') => { // A field condition like "due:<today"
+                position += 1;
+                let operator = chars.get(position).copied().filter(|c| matches!(c, '~' | '<' | '>'));
+                if operator.is_some() {
+                    position += 1;
+                }
+                let value_start = position;
+                let value = read_filter_value(&chars, &mut position)?;
+                parse_condition(&key.to_lowercase(), key_start, operator, &value, value_start, now)?
+            }
+            Some('"') if key.is_empty() => Filter::Word(read_filter_value(&chars, &mut position)?),
+            _ if key.len() > 1 && key.starts_with('#') => { // A tag like "#work"
+                Filter::Tag(TextMatch::Exact(key.trim_start_matches('#').to_owned()))
+            }
+            Some('"') => return Err(filter_error(position, "a quote has to start a word or value")),
+            _ if key.is_empty() => return Err(filter_error(key_start, "expected a term after '-'")),
+            _ => Filter::Word(key),
+        };
+        filters.push(if negated { Filter::Not(Box::new(filter)) } else { filter });
+    }
+    Ok(filters)
+}
+
+
+fn read_filter_value(chars: &[char], position: &mut usize)
+    -> Result<String, Error> { // Read a bare or a "quoted" value, a quoted one may contain \"
+    let mut value = String::new();
+    if chars.get(*position) != Some(&'"') {
+        while let Some(&c) = chars.get(*position).filter(|c| !c.is_whitespace()) {
+            value.push(c);
+            *position += 1;
+        }
+        return Ok(value);
+    }
+
+    let start = *position;
+    *position += 1;
+    loop {
+        match chars.get(*position) {
+            None => return Err(filter_error(start, "the quote is never closed")),
+            Some('"') => {
+                *position += 1;
+                return Ok(value);
+            }
+            Some('\\') if *position + 1 < chars.len() => {
+                value.push(chars[*position + 1]);
+                *position += 2;
+            }
+            Some(&c) => {
+                value.push(c);
+                *position += 1;
+            }
+        }
+    }
+}
+
+
+fn parse_condition(field: &str, field_start: usize, operator: Option<char>, value: &str, value_start: usize,
+                   now: DateTime<Local>) -> Result<Filter, Error> { // Turn "field:value" into a filter
+    if value.is_empty() {
+        return Err(filter_error(value_start, &format!("{}: needs a value", field)));
+    }
+    let bad_value = |expected: &str| {
+        filter_error(value_start, &format!("expected {} after {}:, not '{}'", expected, field, value))
+    };
+    let bad_operator = |operator: char| {
+        filter_error(value_start - 1, &format!("{}: can not be used with '{}'", field, operator))
+    };
+    let text_match = |default: fn(String) -> TextMatch| match operator {
+        None => Ok(default(value.to_owned())),
+        Some('~') => Ok(TextMatch::Fuzzy(value.to_owned())),
+        Some(operator) => Err(bad_operator(operator)),
+    };
+
+    match field {
+        "id" => match operator {
+            None => value.parse().map(Filter::Id).map_err(|_| bad_value("a number")),
+            Some(operator) => Err(bad_operator(operator)),
+        },
+        "name" => Ok(Filter::Name(text_match(TextMatch::Contains)?)),
+        "category" => Ok(Filter::Category(text_match(TextMatch::Exact)?)),
+        "tag" => {
+            let tag = value.trim_start_matches('#');
+            match operator {
+                None => Ok(Filter::Tag(TextMatch::Exact(tag.to_owned()))),
+                Some('~') => Ok(Filter::Tag(TextMatch::Fuzzy(tag.to_owned()))),
+                Some(operator) => Err(bad_operator(operator)),
+            }
+        }
+        "text" => Ok(Filter::Text(text_match(TextMatch::Contains)?)),
+        "is" => match (operator, value.to_lowercase().as_str()) {
+            (None, "ready") => Ok(Filter::Ready),
+            (None, "blocked") => Ok(Filter::Blocked),
+            (None, _) => Err(bad_value("ready or blocked")),
+            (Some(operator), _) => Err(bad_operator(operator)),
+        },
+        "status" => match operator {
+            None => TodoStatus::from_label(value)
+                .map(Filter::Status)
+                .ok_or_else(|| bad_value("open, in-progress, done or cancelled")),
+            Some(operator) => Err(bad_operator(operator)),
+        },
+        "priority" => {
+            let ordering = match operator {
+                None => std::cmp::Ordering::Equal,
+                Some('<') => std::cmp::Ordering::Less,
+                Some('>') => std::cmp::Ordering::Greater,
+                Some(operator) => return Err(bad_operator(operator)),
+            };
+            Priority::from_label(value)
+                .map(|priority| Filter::Priority(ordering, priority))
+                .ok_or_else(|| bad_value("none, low, medium, high or urgent"))
+        }
+        _ => {
+            let date_field = DateField::from_name(field)
+                .ok_or_else(|| filter_error(field_start, &format!("unknown field '{}'", field)))?;
+            let phrase = value.to_lowercase();
+            if phrase == "none" && operator.is_none() {
+                return Ok(Filter::NoDate(date_field));
+            }
+            let utc_midnight = |date| start_of_day(date).map(|start| start.with_timezone(&Utc));
+            let (from, until) = date_range(&phrase, now.date_naive())
+                .and_then(|(first, after)| Some((utc_midnight(first)?, utc_midnight(after)?)))
+                .ok_or_else(|| bad_value("a date like 2024-01-31, today, last-week or none"))?;
+            match operator {
+                None => Ok(Filter::Date(date_field, Some(from), Some(until))),
+                Some('<') => Ok(Filter::Date(date_field, None, Some(from))),
+                Some('>') => Ok(Filter::Date(date_field, Some(until), None)),
+                Some(operator) => Err(bad_operator(operator)),
+            }
+        }
+    }
+}
+
+
+fn filter_error(position: usize, message: &str) -> Error { // Error pointing at a char of the query
+    Error::ParseFilterError(position + 1, message.to_owned())
+}
+
+
+fn search_positions(text: &str, filters: &[Filter]) -> Vec<usize> { // Char positions to highlight in text
+    filters
+        .iter()
+        .filter_map(Filter::highlight)
+        .filter_map(|word| fuzzy_match(text, word))
+        .flatten()
+        .collect()
+}
+
+
+fn fuzzy_match(text: &str, word: &str) -> Option<Vec<usize>> { // Char positions of word in text, ignoring case
+    let text: Vec<char> = text.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    if word.is_empty() {
+        return Some(Vec::new());
+    }
+
+    // A substring is the best match
+    if let Some(start) = text
+        .windows(word.len())
+        .position(|window| window.iter().zip(&word).all(|(&a, &b)| same(a, b))) {
+        return Some((start..start + word.len()).collect());
+    }
+
+    // Otherwise the chars of the word have to appear in order, like "mlk" in "buy milk"
+    let mut chars = text.iter().enumerate();
+    word.iter()
+        .map(|&b| chars.find(|&(_, &a)| same(a, b)).map(|(position, _)| position))
+        .collect()
+}
+// ----------------------------------   END OF SEARCH FUNCTIONS   ----------------------------------
+
+
+// ----------------------------------        CLI FUNCTIONS        ----------------------------------
+fn parse_args() -> (Option<PathBuf>, Command) { // Get the --db flag and the command, exit on errors
+    let mut args = env::args().skip(1);
+    let mut db_flag = None;
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut flags = Vec::new(); // Options without a value
+
+    while let Some(arg) = args.next() {
+        if arg == "--" { // Everything after it is positional, like "-status:done"
+            positional.extend(args.by_ref());
+        } else if arg == "--help" || arg == "-h" {
+            println!("{}", USAGE);
+            process::exit(0);
+        } else if arg == "--db" {
+            db_flag = Some(PathBuf::from(args.next().unwrap_or_else(|| usage_error("--db needs a path"))));
+        } else if let Some(path) = arg.strip_prefix("--db=") {
+            db_flag = Some(PathBuf::from(path));
+        } else if BOOLEAN_FLAGS.contains(&arg.as_str()) {
+            flags.push(arg);
+        } else if arg.starts_with('-') && arg.len() > 1 && !arg.contains(':') && !arg.starts_with("-#") { // "-status:done" and "-#home" are filters
+            let value = args.next().unwrap_or_else(|| usage_error(&format!("{} needs a value", arg)));
+            options.push((arg, value));
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        None => Command::Tui,
+        Some("add") => {
+            let mut inputs = InputStates::default();
+            inputs.name = positional.next().unwrap_or_else(|| usage_error("add needs a name"));
+            for (option, value) in options.drain(..) {
+                match option.as_str() {
+                    "--category" | "-c" => inputs.category = value,
+                    "--tags" | "-g" => inputs.tags = value,
+                    "--parent" => inputs.parent = Some(parse_id(Some(value))),
+                    "--blocked-by" | "-b" => inputs.blocked_by = value,
+                    "--repeat" | "-r" => inputs.repeat = value,
+                    "--text" | "-t" => inputs.text = value,
+                    "--due" | "-d" => inputs.due = value,
+                    "--priority" | "-p" => {
+                        inputs.priority = Priority::from_label(&value)
+                            .unwrap_or_else(|| usage_error(&format!("unknown priority: {}", value)));
+                    }
+                    _ => usage_error(&format!("unknown option for add: {}", option)),
+                }
+            }
+            Command::Add(inputs)
+        }
+        Some("list") => Command::List(parse_query(positional.by_ref()), parse_format(&mut options)),
+        Some("show") => {
+            let id = parse_id(positional.next());
+            Command::Show(id, parse_format(&mut options))
+        }
+        Some("rm") => Command::Remove(parse_id(positional.next())),
+        Some("done") => Command::Done(parse_id(positional.next()), take_flag(&mut flags, "--subtasks")),
+        Some("trash") => Command::Trash(parse_query(positional.by_ref()), parse_format(&mut options)),
+        Some("restore") => Command::Restore(parse_id(positional.next())),
+        Some("purge") => Command::Purge(parse_id(positional.next())),
+        Some("repair") => Command::Repair(take_flag(&mut flags, "--renumber")),
+        Some("categories") => Command::Categories,
+        Some("migrate") => Command::Migrate,
+        Some("rename-category") => {
+            let category = positional.next().unwrap_or_else(|| usage_error("rename-category needs a category"));
+            let new_name = positional.next().unwrap_or_else(|| usage_error("rename-category needs a new name"));
+            Command::RenameCategory(category, new_name)
+        }
+        Some(other) => usage_error(&format!("unknown command: {}", other)),
+    };
+
+    if let Some(option) = options.first().map(|(option, _)| option).or(flags.first()) {
+        usage_error(&format!("unknown option: {}", option));
+    }
+    if let Some(extra) = positional.next() {
+        usage_error(&format!("unexpected argument: {}", extra));
+    }
+    (db_flag, command)
+}
+
+
+fn parse_query(args: impl Iterator<Item = String>) -> Vec<Filter> { // Join the arguments into a filter
+    let query = args.collect::<Vec<_>>().join(" ");
+    match parse_filter(&query, Local::now()) {
+        Ok(filter) => filter,
+        Err(Error::ParseFilterError(column, message)) => usage_error(&format!(
+            "invalid filter: {}\n    {}\n    {}^", message, query, " ".repeat(column - 1)
+        )),
+        Err(error) => usage_error(&error.to_string()),
+    }
+}
+
+
+fn take_flag(flags: &mut Vec<String>, flag: &str) -> bool { // Remove a flag, true if it was given
+    let given = flags.iter().any(|given| given == flag);
+    flags.retain(|given| given != flag);
+    given
+}
+
+
+fn parse_id(arg: Option<String>) -> usize {
+    let arg = arg.unwrap_or_else(|| usage_error("missing TODO id"));
+    arg.parse().unwrap_or_else(|_| usage_error(&format!("not a TODO id: {}", arg)))
+}
+
+
+fn parse_format(options: &mut Vec<(String, String)>) -> OutputFormat { // Take the --format option
+    let mut format = OutputFormat::Table;
+    options.retain(|(option, value)| {
+        if option != "--format" && option != "-f" {
+            return true;
+        }
+        format = match value.as_str() {
+            "table" => OutputFormat::Table,
+            "json" => OutputFormat::Json,
+            "tsv" => OutputFormat::Tsv,
+            _ => usage_error(&format!("unknown format: {}", value)),
+        };
+        false
+    });
+    format
+}
+
+
+fn usage_error(message: &str) -> ! { // Explain a bad command line and exit
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+
+struct Output<W: Write>(W); // Command output, its write errors are not mistaken for db errors
+
+impl<W: Write> Output<W> {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<(), Error> { // Called by writeln!
+        self.0.write_fmt(args).map_err(Error::WriteOutputError)
+    }
+}
+
+
+fn run_command(command: Command) -> Result<(), Error> { // Run a command without the interface
+    let mut out = Output(io::stdout().lock());
+    if let Some(legacy_db) = find_legacy_db() { // Writing first would leave the old list behind for good
+        match command {
+            Command::Migrate => {}
+            _ if command.writes() => return Err(Error::LegacyDBFound(legacy_db)),
+            _ => eprintln!("warning: TODOs of an older version are still in {}, run `todo-cli migrate` to move them \
+                            to {}", legacy_db.display(), db_path().display()),
+        }
+    }
+    match command {
+        Command::Tui => {}
+        Command::Migrate => match find_legacy_db() {
+            Some(legacy_db) => {
+                migrate_legacy_db(&legacy_db)?;
+                writeln!(out, "Moved {} to {}", legacy_db.display(), db_path().display())?;
+            }
+            None => writeln!(out, "There is no database of an older version to move")?,
+        },
+        Command::Add(inputs) => {
+            let mut store = read_db(db_path())?;
+            let change = add_todo_from_input(&mut store, &inputs)?;
+            write_db(db_path(), &store)?;
+            if let Some(todo) = change.todo() {
+                writeln!(out, "Added TODO {}: {}", todo.id, todo.name)?;
+            }
+            record_change(change)?;
+        }
+        Command::List(ref filter, format) | Command::Trash(ref filter, format) => {
+            let trash = matches!(command, Command::Trash(..));
+            let all = read_db(db_path())?.todos;
+            let ids = index_by_id(&all);
+            let todos: Vec<Todo> = all
+                .iter()
+                .filter(|todo| todo.is_trashed() == trash && filter.iter().all(|filter| filter.matches(todo, &all, &ids)))
+                .cloned()
+                .collect();
+            match format {
+                OutputFormat::Table => print_todo_table(&mut out, &todos)?,
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&todos)?)?,
+                OutputFormat::Tsv => print_todo_tsv(&mut out, &todos)?,
+            }
+        }
+        Command::Show(id, format) => {
+            let todos = read_db(db_path())?.todos;
+            let todo = todos.iter().find(|todo| todo.id == id && !todo.is_trashed()).ok_or(Error::TodoNotFound(id))?;
+            match format {
+                OutputFormat::Table => print_todo_detail(&mut out, todo, &todos)?,
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(todo)?)?,
+                OutputFormat::Tsv => print_todo_tsv(&mut out, std::slice::from_ref(todo))?,
+            }
+        }
+        Command::Remove(id) => {
+            let mut store = read_db(db_path())?;
+            let change = trash_todo_with_id(&mut store, id)?;
+            write_db(db_path(), &store)?;
+            if let Some(todo) = change.todo() {
+                writeln!(out, "Moved TODO {} to the trash: {}", todo.id, todo.name)?;
+            }
+            record_change(change)?;
+        }
+        Command::Done(id, subtasks) => {
+            let mut store = read_db(db_path())?;
+            let change = if subtasks {
+                complete_with_subtasks(&mut store, id)?
+            } else {
+                change_status_with_id(&mut store, id, TodoStatus::Done)?
+            };
+            if matches!(&change, Change::Batch { changes, .. } if changes.is_empty()) {
+                writeln!(out, "TODO {} and its subtasks are already done", id)?;
+                return Ok(());
+            }
+            write_db(db_path(), &store)?;
+            let name = store.todos.iter().find(|todo| todo.id == id).map_or("", |todo| todo.name.as_str());
+            if subtasks {
+                writeln!(out, "Marked TODO {} and its subtasks as done: {}", id, name)?;
+            } else {
+                writeln!(out, "Marked TODO {} as done: {}", id, name)?;
+            }
+            for next in change.added() { // Recurring to_dos come back
+                writeln!(out, "Added the next one as TODO {}{}", next.id, next.due.map_or_else(String::new, |due| {
+                    format!(", due {}", due.with_timezone(&Local).format("%Y-%m-%d %H:%M"))
+                }))?;
+            }
+            record_change(change)?;
+        }
+        Command::Restore(id) => {
+            let mut store = read_db(db_path())?;
+            let change = restore_todo_with_id(&mut store, id)?;
+            write_db(db_path(), &store)?;
+            if let Some(todo) = change.todo() {
+                writeln!(out, "Restored TODO {}: {}", todo.id, todo.name)?;
+            }
+            record_change(change)?;
+        }
+        Command::Purge(id) => {
+            let mut store = read_db(db_path())?;
+            if !store.todos.iter().any(|todo| todo.id == id && todo.is_trashed()) { // Only empty the trash
+                return Err(Error::TodoNotFound(id));
+            }
+            let change = remove_todo_with_id(&mut store, id)?;
+            write_db(db_path(), &store)?;
+            if let Some(todo) = change.todo() {
+                writeln!(out, "Purged TODO {}: {}", todo.id, todo.name)?;
+            }
+            record_change(change)?;
+        }
+        Command::Repair(renumber) => {
+            let mut store = read_db(db_path())?;
+            let changes = store.repair_ids(renumber);
+            write_db(db_path(), &store)?;
+            if !changes.is_empty() { // The history refers to to_dos by their old ids
+                write_history(&History::default())?;
+            }
+            if changes.is_empty() {
+                writeln!(out, "All TODO ids are unique")?;
+            }
+            for (old_id, new_id, name) in changes {
+                writeln!(out, "TODO {} is now {}: {}", old_id, new_id, name)?;
+            }
+        }
+        Command::Categories => {
+            let todos = read_db(db_path())?.todos;
+            writeln!(out, "{:<8} CATEGORY", "TODOS")?;
+            for category in categories(&todos) {
+                let count = todos
+                    .iter()
+                    .filter(|todo| !todo.is_trashed() && todo.category.to_uppercase() == category)
+                    .count();
+                writeln!(out, "{:<8} {}", count, category_label(&category))?;
+            }
+        }
+        Command::RenameCategory(category, new_name) => {
+            let (category, new_name) = (category.to_uppercase(), new_name.trim().to_uppercase());
+            let mut store = read_db(db_path())?;
+            let count = store.todos.iter().filter(|todo| todo.category.to_uppercase() == category).count();
+            let change = rename_category(&mut store, &category, &new_name)?;
+            write_db(db_path(), &store)?;
+            record_change(change)?;
+            writeln!(out, "Moved {} TODO{} from {} to {}",
+                     count, if count == 1 { "" } else { "s" }, category_label(&category), category_label(&new_name))?;
+        }
+    }
+    Ok(())
 }
-// ----------------------------------  END OF UI BLOCK FUNCTIONS  ----------------------------------
-
-
-// ----------------------------------     DB-RELATED FUNCTIONS    ----------------------------------
-fn read_db() -> Result<Vec<Todo>, Error> { // Get vector containing all to_dos from the db
-    let reading_result = fs::read_to_string(DB_PATH);
 
-    if reading_result.is_err() {
-        fs::write(DB_PATH, "[]".to_owned()).expect("Can create a file");
-    }
-    let parsing_result: Result<Vec<Todo>, _> = match reading_result {
-        Ok(contents) => { serde_json::from_str(contents.as_str()) }
-        Err(_) => { Ok(Vec::new()) }
-    };
 
-    match parsing_result { // Check if the db is empty, return empty vector if so
-        Ok(parsed) => {Ok(parsed)}
-        Err(_) => {Ok(Vec::new())}
+fn print_todo_table(out: &mut Output<impl Write>, todos: &[Todo]) -> Result<(), Error> { // Print one line per to_do
+    writeln!(out, "{:<8} {:<12} {:<8} {:<16} {:<12} NAME",
+             "ID", "STATUS", "PRIORITY", "DUE", "CATEGORY")?;
+    for todo in todos {
+        writeln!(
+            out,
+            "{:<8} {:<12} {:<8} {:<16} {:<12} {}",
+            todo.id,
+            todo.status.label(),
+            todo.priority.label(),
+            todo.due.map_or_else(String::new, |due| {
+                due.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+            }),
+            todo.category,
+            match todo.tags_not_in(&[&todo.name]) { // The name shows its own #tag words
+                tags if tags.is_empty() => todo.name.clone(),
+                tags => format!("{} {}", todo.name, format_tags(&tags)),
+            }
+        )?;
     }
+    Ok(())
 }
 
 
-fn add_todo_from_input_to_db(input_states: &InputStates)
-    -> Result<Vec<Todo>, Error> { // Add to_do to the db
-    let mut rng = rand::thread_rng();
-    let db_content = fs::read_to_string(DB_PATH)?;
-    let mut parsed: Vec<Todo> = serde_json::from_str(&db_content)?;
-
-    let default_todo = Todo {
-        id: rng.gen_range(0, 9999999),
-        name: input_states.name.to_owned(),
-        category: input_states.category.to_uppercase().to_owned(),
-        text: input_states.text.to_owned(),
-        created_at: Utc::now(),
-    };
-
-    parsed.push(default_todo);
-    fs::write(DB_PATH, &serde_json::to_vec(&parsed)?)?;
-
-    Ok(parsed)
+fn print_todo_tsv(out: &mut Output<impl Write>, todos: &[Todo]) -> Result<(), Error> { // Print a header and one row per to_do
+    writeln!(out, "{}", TODO_FIELDS.join("\t"))?;
+    for todo in todos {
+        let value = serde_json::to_value(todo)?;
+        let row: Vec<String> = TODO_FIELDS
+            .iter()
+            .map(|field| match &value[*field] {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::Array(values) if values.iter().all(|value| value.is_string() || value.is_u64()) => values
+                    .iter() // Tags and ids, which have no commas; the checklist stays JSON
+                    .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_owned))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                serde_json::Value::String(text) => text
+                    .replace('\\', "\\\\")
+                    .replace('\t', "\\t")
+                    .replace('\n', "\\n")
+                    .replace('\r', "\\r"),
+                other => other.to_string(),
+            })
+            .collect();
+        writeln!(out, "{}", row.join("\t"))?;
+    }
+    Ok(())
 }
 
 
-fn remove_todo_at_index(todo_list_state: &mut ListState)
-    -> Result<(), Error> { // Remove to_do from db
-    if let Some(selected) = todo_list_state.selected() {
-        let db_content = fs::read_to_string(DB_PATH)?;
-        let mut parsed: Vec<Todo> = serde_json::from_str(&db_content)?;
-        parsed.remove(selected);
-        fs::write(DB_PATH, &serde_json::to_vec(&parsed)?)?;
-        todo_list_state.select(
-            if selected >= 1 {
-                Some(selected - 1)
-            } else {
-                None
-            }
-        );
+fn print_todo_detail(out: &mut Output<impl Write>, todo: &Todo, todos: &[Todo])
+    -> Result<(), Error> { // Print every field of a to_do, todos are where its blockers and dependents are found
+    let now = Utc::now();
+    writeln!(out, "ID:         {}", todo.id)?;
+    if let Some(uuid) = &todo.uuid {
+        writeln!(out, "UUID:       {}", uuid)?;
+    }
+    writeln!(out, "Name:       {}", todo.name)?;
+    writeln!(out, "Category:   {}", todo.category)?;
+    if let Some(parent) = todo.parent {
+        writeln!(out, "Parent:     {}", parent)?;
+    }
+    if !todo.tags.is_empty() {
+        writeln!(out, "Tags:       {}", format_tags(&todo.tags))?;
+    }
+    if !todo.blocked_by.is_empty() {
+        writeln!(out, "Blocked By: {}{}", format_ids(&todo.blocked_by),
+                 if is_blocked(todos, &index_by_id(todos), todo) { "" } else { " (all done)" })?;
+    }
+    let dependents = dependents(todos, todo.id);
+    if !dependents.is_empty() {
+        writeln!(out, "Blocks:     {}", format_ids(&dependents))?;
+    }
+    writeln!(out, "Text:       {}", todo.text)?;
+    if let Some(percentage) = todo.checklist_percentage() {
+        writeln!(out, "Checklist:  {}%", percentage)?;
+        for item in &todo.checklist {
+            writeln!(out, "    {} {}", if item.checked { "[x]" } else { "[ ]" }, item.text)?;
+        }
+    }
+    writeln!(out, "Priority:   {}", todo.priority.label())?;
+    writeln!(out, "Status:     {}", todo.status.label())?;
+    if let Some(due) = todo.due {
+        writeln!(out, "Due:        {} ({})",
+                 due.with_timezone(&Local).format("%Y-%m-%d %H:%M"), format_countdown(due, now))?;
+    }
+    if let Some(recurrence) = &todo.recurrence {
+        writeln!(out, "Repeats:    {}", recurrence.describe())?;
+    }
+    writeln!(out, "Created At: {}", todo.created_at)?;
+    if let Some(updated_at) = todo.updated_at {
+        writeln!(out, "Updated At: {}", updated_at)?;
+    }
+    if let Some(completed_at) = todo.completed_at {
+        writeln!(out, "Completed:  {}", completed_at)?;
     }
     Ok(())
 }
-// ---------------------------------- END OF DB-RELATED FUNCTIONS ----------------------------------
+// ----------------------------------    END OF CLI FUNCTIONS     ----------------------------------
 
 
 // ----------------------------------           FN MAIN           ----------------------------------
 fn main() {
+    // Find the config and the db before touching the terminal, so errors stay readable
+    let (db_flag, command) = parse_args();
+    match read_config() {
+        Ok(config) => { CONFIG.get_or_init(|| config); }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(error.exit_code());
+        }
+    }
+    DB_PATH.get_or_init(|| resolve_db_path(db_flag));
+    if let Err(error) = fs::create_dir_all(db_dir(db_path())) {
+        eprintln!("can not create {}: {}", db_dir(db_path()).display(), error);
+        process::exit(1);
+    }
+
+    if !matches!(command, Command::Tui) { // Run the command and skip the interface
+        match run_command(command) {
+            Err(Error::WriteOutputError(error)) if error.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(error) => {
+                eprintln!("error: {}", error);
+                process::exit(error.exit_code());
+            }
+            Ok(()) => {}
+        }
+        return;
+    }
+
     // Create a Terminal
     enable_raw_mode().expect("");
     let mut stdout = io::stdout();
@@ -451,6 +4413,7 @@ fn main() {
 
     let (tx, rx) = mpsc::channel();
     let tick_rate = Duration::milliseconds(200);
+    watch_db(tx.clone());
 
     thread::spawn(move || { // Input-capturing thread
         let mut last_tick = Instant::now();
@@ -466,29 +4429,25 @@ fn main() {
                 }
             }
 
-            if last_tick.elapsed() >= tick_rate.to_std().expect("") {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
+            if last_tick.elapsed() >= tick_rate.to_std().expect("")
+                && tx.send(Event::Tick).is_ok() {
+                last_tick = Instant::now();
             }
         }
     });
 
-    let menu_titles = vec![
-        "Home", "TODOs", "Add", "Delete", "Quit"
-    ]; // Stores all menu tabs
-    let mut active_menu_item = MenuItem::Home;
+    let menu_titles = [
+        "Home", "TODOs", "Add", "Delete", "Backups", "Trash", "Quit", "Recovery"
+    ]; // Stores all menu tabs, the last one is only shown for a corrupt db
 
-    let mut todo_list_state = ListState::default(); // Stores the current selected to_do
-    todo_list_state.select(Some(0));
-
-    let mut inputs = InputStates {  // Stores current values of all inputs
-        name: String::new(),
-        category: String::new(),
-        text: String::new(),
-    };
-
-    let mut focused_input = FocusedInput::None; // Stores the current focused input
+    let mut app = App::new(); // Stores the loaded to_dos and the interface state
+    let duplicates = app.store.duplicate_ids(); // Point out ids that older versions handed out twice
+    if !duplicates.is_empty() {
+        app.status_message = Some(format!(
+            "{} TODO ids are used more than once, run 'todo-cli repair' to fix them",
+            duplicates.len()
+        ));
+    }
 
     // Main loop
     loop {
@@ -508,10 +4467,14 @@ fn main() {
                 .split(size);
 
             // Render the fake copyright block
-            rect.render_widget(copyright_block(), chunks[2]);
+            rect.render_widget(copyright_block(&app.status_message), chunks[2]);
 
             // Render the top menu
-            let menu = menu_titles
+            let shown_titles = match app.db_error {
+                Some(_) => &menu_titles[..],
+                None => &menu_titles[..menu_titles.len() - 1],
+            };
+            let menu = shown_titles
                 .iter()
                 .map(|t| {
                     let (first, rest) = t.split_at(1);
@@ -528,7 +4491,7 @@ fn main() {
                 .collect();
 
             let tabs = Tabs::new(menu)
-                .select(active_menu_item.into())
+                .select(app.active_menu_item.into())
                 .block(Block::default().title("Menu").borders(Borders::ALL))
                 .style(Style::default().fg(Color::White))
                 .highlight_style(Style::default().fg(Color::LightYellow))
@@ -536,20 +4499,126 @@ fn main() {
 
             rect.render_widget(tabs, chunks[0]);
 
-            match active_menu_item {
+            match app.active_menu_item {
                 MenuItem::Home => { // Render the "home" tab
-                    rect.render_widget(render_home(), chunks[1])
+                    rect.render_widget(render_home(&app.legacy_db), chunks[1])
                 }
                 MenuItem::TODOs => { // Render the "TODOs" tab
+                    let mut todos_chunk = chunks[1];
+                    if app.db_error.is_none() { // Put the saved views and the categories on the left
+                        let side_chunks = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints(
+                                [Constraint::Percentage(15), Constraint::Percentage(85)].as_ref(),
+                            )
+                            .split(chunks[1]);
+                        let mut categories_chunk = side_chunks[0];
+                        if !config().views.is_empty() {
+                            let views_chunks = Layout::default()
+                                .direction(Direction::Vertical)
+                                .constraints(
+                                    [Constraint::Length(config().views.len() as u16 + 3), Constraint::Min(3)].as_ref(),
+                                )
+                                .split(side_chunks[0]);
+                            let mut views_state = ListState::default();
+                            views_state.select(Some(app.list_options.view));
+                            rect.render_stateful_widget(
+                                render_views(app.counts()),
+                                views_chunks[0],
+                                &mut views_state,
+                            );
+                            categories_chunk = views_chunks[1];
+                        }
+                        let mut categories_state = ListState::default();
+                        categories_state.select(Some(
+                            app.list_options.category
+                                .as_ref()
+                                .and_then(|shown| app.counts().categories.iter().position(|(category, _)| category == shown))
+                                .map_or(0, |position| position + 1)
+                        ));
+                        let tags_chunks = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                            .split(categories_chunk);
+                        rect.render_stateful_widget(
+                            render_categories(app.counts()),
+                            tags_chunks[0],
+                            &mut categories_state,
+                        );
+                        rect.render_widget(render_tags(app.counts()), tags_chunks[1]);
+                        todos_chunk = side_chunks[1];
+                    }
                     let todos_chunks = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints(
                             [Constraint::Percentage(20), Constraint::Percentage(80)].as_ref(),
                         )
+                        .split(todos_chunk);
+                    match &app.db_error {
+                        None => {
+                            let mut list_chunk = todos_chunks[0];
+                            let prompt = match &app.renaming { // The rename prompt takes the place of the search
+                                Some(category) => Some(render_rename(category, &app.rename_input)),
+                                None if app.focused_input == FocusedInput::Search
+                                    || !app.list_options.search.is_empty() => Some(
+                                    render_search(&app.list_options.search, &app.search_error, &app.focused_input)
+                                ),
+                                None => None,
+                            };
+                            if let Some(prompt) = prompt {
+                                let search_chunks = Layout::default()
+                                    .direction(Direction::Vertical)
+                                    .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+                                    .split(todos_chunks[0]);
+                                rect.render_widget(prompt, search_chunks[0]);
+                                list_chunk = search_chunks[1];
+                            }
+                            let (left, right) =
+                                render_todos(&app.store.todos, app.rows(), &app.todo_list_state, &app.list_options);
+                            rect.render_stateful_widget(left, list_chunk, &mut app.todo_list_state);
+                            let detail_chunks = Layout::default() // The checklist goes under the details
+                                .direction(Direction::Vertical)
+                                .constraints([Constraint::Length(4), Constraint::Min(3)].as_ref())
+                                .split(todos_chunks[1]);
+                            rect.render_widget(right, detail_chunks[0]);
+                            let checklist = render_checklist(
+                                app.checklist(), &app.focused_input, &app.checklist_input, app.checklist_editing,
+                            );
+                            rect.render_stateful_widget(checklist, detail_chunks[1], &mut app.checklist_state);
+                        }
+                        Some(error) => {
+                            rect.render_widget(render_db_error(error, &app.quarantine), chunks[1]);
+                        }
+                    }
+                }
+                MenuItem::Recovery => { // Render the "Recovery" tab
+                    if let Some(error) = &app.db_error {
+                        let recovery_chunks = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(5), Constraint::Min(3)].as_ref())
+                            .split(chunks[1]);
+                        let (help, file) = render_recovery(error, &app.quarantine);
+                        rect.render_widget(help, recovery_chunks[0]);
+                        rect.render_widget(file, recovery_chunks[1]);
+                    }
+                }
+                MenuItem::Backups => { // Render the "Backups" tab
+                    let backups_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(4), Constraint::Min(3)].as_ref())
+                        .split(chunks[1]);
+                    let (help, list) = render_backups(&list_backups(db_path()).unwrap_or_default());
+                    rect.render_widget(help, backups_chunks[0]);
+                    rect.render_stateful_widget(list, backups_chunks[1], &mut app.backup_list_state);
+                }
+                MenuItem::Trash => { // Render the "Trash" tab
+                    let trash_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(4), Constraint::Min(3)].as_ref())
                         .split(chunks[1]);
-                    let (left, right) = render_todos(&todo_list_state);
-                    rect.render_stateful_widget(left, todos_chunks[0], &mut todo_list_state);
-                    rect.render_widget(right, todos_chunks[1]);
+                    let (help, list) = render_trash(&app.store.todos);
+                    rect.render_widget(help, trash_chunks[0]);
+                    rect.render_stateful_widget(list, trash_chunks[1], &mut app.trash_list_state);
                 }
                 MenuItem::Add => { // Render the "Add to_do" tab
                     let add_chunks = Layout::default()
@@ -559,23 +4628,38 @@ fn main() {
                                 Constraint::Min(3),
                                 Constraint::Percentage(20),
                                 Constraint::Percentage(20),
+                                Constraint::Length(3),
+                                Constraint::Length(3),
+                                Constraint::Length(3),
+                                Constraint::Length(3),
+                                Constraint::Length(3),
                                 Constraint::Percentage(60),
                             ].as_ref()
                         ).split(chunks[1]);
-                    let (help, name, category, text) =
-                        render_add(&inputs, &focused_input);
+                    let (help, name, category, tags, priority, due, repeat, blocked_by, text) =
+                        render_add(&app.inputs, &app.focused_input, app.editing);
 
                     rect.render_widget(help, add_chunks[0]);
                     rect.render_widget(name, add_chunks[1]);
                     rect.render_widget(category, add_chunks[2]);
-                    rect.render_widget(text, add_chunks[3]);
+                    rect.render_widget(tags, add_chunks[3]);
+                    rect.render_widget(priority, add_chunks[4]);
+                    rect.render_widget(due, add_chunks[5]);
+                    rect.render_widget(repeat, add_chunks[6]);
+                    rect.render_widget(blocked_by, add_chunks[7]);
+                    rect.render_widget(text, add_chunks[8]);
                 }
             }
 
         }).expect("Can draw"); // End of the terminal.draw()
 
-        match rx.recv().expect("Input received") {
-            Event::Input(event) => match (event.code, focused_input) {
+        let received = rx.recv().expect("Input received");
+        if let Event::Input(_) = received { // Messages last until the next key press
+            app.status_message = None;
+        }
+
+        match received {
+            Event::Input(event) => match (event.code, app.focused_input) {
                 (KeyCode::Char('q'), FocusedInput::None) => { // Quit
                     disable_raw_mode().expect("");
                     terminal.show_cursor().expect("");
@@ -583,42 +4667,256 @@ fn main() {
                 }
 
                 // Switch between the tabs
-                (KeyCode::Char('h'), FocusedInput::None) => active_menu_item = MenuItem::Home,
-                (KeyCode::Char('t'), FocusedInput::None) => active_menu_item = MenuItem::TODOs,
-                (KeyCode::Char('a'), FocusedInput::None) => active_menu_item = MenuItem::Add,
+                (KeyCode::Char('h'), FocusedInput::None) => app.active_menu_item = MenuItem::Home,
+                (KeyCode::Char('t'), FocusedInput::None) => app.active_menu_item = MenuItem::TODOs,
+                (KeyCode::Char('a'), FocusedInput::None) => app.active_menu_item = MenuItem::Add,
+                (KeyCode::Char('b'), FocusedInput::None) => app.active_menu_item = MenuItem::Backups,
+                (KeyCode::Char('T'), FocusedInput::None) => app.active_menu_item = MenuItem::Trash,
 
-                (KeyCode::Char('d'), FocusedInput::None) => { // Remove selected to_do
-                    remove_todo_at_index(&mut todo_list_state).expect("can remove todos");
+                (KeyCode::Char('m'), FocusedInput::None) if app.legacy_db.is_some() => { // Move the old db
+                    let message = match find_legacy_db() { // The new db may have been filled meanwhile
+                        Some(path) => match migrate_legacy_db(&path) {
+                            Ok(()) => format!("Moved {} to {}", path.display(), db_path().display()),
+                            Err(error) => format!("Could not move {}: {}", path.display(), error),
+                        },
+                        None => "There is no database of an older version to move".to_owned(),
+                    };
+                    app.reload();
+                    app.status_message = Some(message);
                 }
 
-                (KeyCode::Down, FocusedInput::None) => { // Select the lower to_do in the list
-                    if let Some(selected) = todo_list_state.selected() {
-                        let amount_pets = read_db().expect("can fetch pet list").len();
-                        if selected >= amount_pets - 1 {
-                            todo_list_state.select(Some(0));
-                        } else {
-                            todo_list_state.select(Some(selected + 1));
+                // Undo and redo changes
+                (KeyCode::Char('u'), FocusedInput::None) => app.undo(),
+                (KeyCode::Char('r'), FocusedInput::None) if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.redo();
+                }
+
+                // Handle a corrupt db
+                (KeyCode::Char('r'), FocusedInput::None) if app.active_menu_item == MenuItem::Recovery => {
+                    app.reload();
+                }
+                (KeyCode::Char('r'), FocusedInput::None) if app.db_error.is_some() => {
+                    app.active_menu_item = MenuItem::Recovery;
+                }
+                (KeyCode::Char('n'), FocusedInput::None)
+                if app.active_menu_item == MenuItem::Recovery && app.quarantine.is_some() => {
+                    match write_db(db_path(), &Store::default()).and_then(|()| write_history(&History::default())) {
+                        Ok(()) => {
+                            let message = app.quarantine.take().map(|path| {
+                                format!("Started a new list, the corrupt one is kept at {}", path)
+                            });
+                            app.reload();
+                            app.status_message = message;
                         }
+                        Err(error) => app.status_message = Some(error.to_string()),
                     }
                 }
-                (KeyCode::Up, FocusedInput::None) => { // Select the higher to_do in the list
-                    if let Some(selected) = todo_list_state.selected() {
-                        let amount_pets = read_db().expect("can fetch pet list").len();
-                        if selected > 0 {
-                            todo_list_state.select(Some(selected - 1));
-                        } else {
-                            todo_list_state.select(Some(amount_pets - 1));
-                        }
+
+                (KeyCode::Char('d'), FocusedInput::None) if app.active_menu_item == MenuItem::Trash => {
+                    app.change_selected_trash(remove_todo_with_id); // Purge the selected to_do
+                }
+                (KeyCode::Enter, FocusedInput::None) if app.active_menu_item == MenuItem::Trash => {
+                    app.change_selected_trash(restore_todo_with_id);
+                }
+                (KeyCode::Down, FocusedInput::None) if app.active_menu_item == MenuItem::Trash => {
+                    app.select_next_trash();
+                }
+                (KeyCode::Up, FocusedInput::None) if app.active_menu_item == MenuItem::Trash => {
+                    app.select_previous_trash();
+                }
+
+                (KeyCode::Char('d'), FocusedInput::None) => { // Remove selected to_do
+                    app.remove_selected();
+                }
+
+                (KeyCode::Char('x'), FocusedInput::None) => { // Toggle selected to_do done
+                    app.change_selected_status(TodoStatus::toggled_done);
+                }
+                (KeyCode::Char('s'), FocusedInput::None) => { // Cycle selected to_do status
+                    app.change_selected_status(TodoStatus::next);
+                }
+
+                // Work with subtasks
+                (KeyCode::Char('X'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.complete_selected_with_subtasks();
+                }
+                (KeyCode::Char('>'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.set_selected_parent(true);
+                }
+                (KeyCode::Char('<'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.set_selected_parent(false);
+                }
+                (KeyCode::Char('A'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    if let Some(index) = app.selected_index() { // Add a subtask in the Add tab
+                        let id = app.store.todos[index].id;
+                        app.folded.retain(|&folded| folded != id);
+                        app.invalidate_rows();
+                        app.editing = None;
+                        app.inputs = InputStates::default();
+                        app.inputs.parent = Some(id);
+                        app.active_menu_item = MenuItem::Add;
+                        app.focused_input = FocusedInput::Name;
+                    }
+                }
+                (KeyCode::Char('e'), FocusedInput::None) => { // Edit selected to_do in the Add tab
+                    if let Some(index) = app.selected_index() {
+                        let todo = &app.store.todos[index];
+                        app.inputs = InputStates::from_todo(todo);
+                        app.editing = Some(todo.id);
+                        app.active_menu_item = MenuItem::Add;
+                        app.focused_input = FocusedInput::Name;
+                    }
+                }
+                (KeyCode::Char('c'), FocusedInput::None) => { // Hide or show completed to_dos
+                    app.list_options.show_completed = !app.list_options.show_completed;
+                    app.invalidate_rows();
+                    app.todo_list_state.select(Some(0));
+                }
+                // Switch between the saved views
+                (KeyCode::Right, FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.select_view((app.list_options.view + 1) % (config().views.len() + 1));
+                }
+                (KeyCode::Left, FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.select_view(app.list_options.view.checked_sub(1).unwrap_or(config().views.len()));
+                }
+                (KeyCode::Char(c @ '0'..='9'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.select_view(c.to_digit(10).unwrap_or(0) as usize);
+                }
+
+                (KeyCode::Char('/'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.focused_input = FocusedInput::Search; // Open the search prompt
+                }
+                (KeyCode::Char('#'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.set_search("#".to_owned()); // Search for a tag
+                    app.focused_input = FocusedInput::Search;
+                }
+
+                // Check, add, edit, delete and move the checklist items of the selected to_do
+                (KeyCode::Char('l'), FocusedInput::None)
+                    if app.active_menu_item == MenuItem::TODOs && app.selected_index().is_some() => {
+                    app.checklist_state.select(Some(0));
+                    app.focused_input = FocusedInput::Checklist;
+                }
+                (KeyCode::Down, FocusedInput::Checklist) => app.select_next_checklist_item(),
+                (KeyCode::Up, FocusedInput::Checklist) => app.select_previous_checklist_item(),
+                (KeyCode::Char(' ') | KeyCode::Char('x'), FocusedInput::Checklist) => app.toggle_checklist_item(),
+                (KeyCode::Char('n'), FocusedInput::Checklist) => {
+                    app.checklist_input.clear();
+                    app.checklist_editing = None;
+                    app.focused_input = FocusedInput::ChecklistItem;
+                }
+                (KeyCode::Char('e') | KeyCode::Enter, FocusedInput::Checklist) => {
+                    if let Some(item) = app.selected_checklist_item() {
+                        app.checklist_input = app.checklist()[item].text.clone();
+                        app.checklist_editing = Some(item);
+                        app.focused_input = FocusedInput::ChecklistItem;
+                    }
+                }
+                (KeyCode::Char('d'), FocusedInput::Checklist) => app.remove_checklist_item(),
+                (KeyCode::Char('K'), FocusedInput::Checklist) => app.move_checklist_item(false),
+                (KeyCode::Char('J'), FocusedInput::Checklist) => app.move_checklist_item(true),
+                (KeyCode::Char(c), FocusedInput::ChecklistItem) => app.checklist_input.push(c),
+                (KeyCode::Backspace, FocusedInput::ChecklistItem) => { app.checklist_input.pop(); }
+                (KeyCode::Enter, FocusedInput::ChecklistItem) => app.save_checklist_item(),
+                (KeyCode::Esc, FocusedInput::ChecklistItem) => { // Keep the item as it is
+                    app.focused_input = FocusedInput::Checklist;
+                }
+
+                // Pick, group, fold and rename categories
+                (KeyCode::Char(']'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.select_category(true);
+                }
+                (KeyCode::Char('['), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.select_category(false);
+                }
+                (KeyCode::Char('g'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.toggle_group();
+                }
+                (KeyCode::Enter, FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    app.toggle_collapsed();
+                }
+                (KeyCode::Char('R'), FocusedInput::None) if app.active_menu_item == MenuItem::TODOs => {
+                    if let Some(category) = app.selected_category() {
+                        app.rename_input = category.clone();
+                        app.renaming = Some(category);
+                        app.focused_input = FocusedInput::Rename;
                     }
                 }
+                (KeyCode::Char(c), FocusedInput::Rename) => app.rename_input.push(c),
+                (KeyCode::Backspace, FocusedInput::Rename) => { app.rename_input.pop(); }
+                (KeyCode::Enter, FocusedInput::Rename) => {
+                    app.rename_selected_category();
+                    app.focused_input = FocusedInput::None;
+                }
+                (KeyCode::Esc, FocusedInput::Rename) => { // Keep the category as it is
+                    app.renaming = None;
+                    app.focused_input = FocusedInput::None;
+                }
+                (KeyCode::Char('p'), FocusedInput::None) => { // Toggle sorting by priority
+                    app.set_sort(match app.list_options.sort.first() {
+                        Some(SortKey::Priority) => vec![SortKey::Manual],
+                        _ => vec![SortKey::Priority],
+                    });
+                }
+                // Move the selected to_do in the manual order
+                (KeyCode::Char('K'), FocusedInput::None) => app.move_selected(|from, _| from.saturating_sub(1)),
+                (KeyCode::Char('J'), FocusedInput::None) => app.move_selected(|from, _| from + 1),
+                (KeyCode::Home, FocusedInput::None) => app.move_selected(|_, _| 0),
+                (KeyCode::End, FocusedInput::None) => app.move_selected(|_, amount| amount.saturating_sub(1)),
+
+                (KeyCode::Char('o'), FocusedInput::None) => app.cycle_sort(false), // Change the sort order
+                (KeyCode::Char('O'), FocusedInput::None) => app.cycle_sort(true), // Change how ties are sorted
+
+                (KeyCode::Down, FocusedInput::None) if app.active_menu_item == MenuItem::Backups => {
+                    let amount = list_backups(db_path()).map(|backups| backups.len()).unwrap_or(0);
+                    app.backup_list_state.select(match app.backup_list_state.selected() {
+                        Some(selected) if selected + 1 < amount => Some(selected + 1),
+                        _ => Some(0),
+                    });
+                }
+                (KeyCode::Up, FocusedInput::None) if app.active_menu_item == MenuItem::Backups => {
+                    let amount = list_backups(db_path()).map(|backups| backups.len()).unwrap_or(0);
+                    app.backup_list_state.select(match app.backup_list_state.selected() {
+                        Some(selected) if selected > 0 => Some(selected - 1),
+                        _ => Some(amount.saturating_sub(1)),
+                    });
+                }
+
+                // Select the lower or higher to_do in the list
+                (KeyCode::Down, FocusedInput::None | FocusedInput::Search) => app.select_next(),
+                (KeyCode::Up, FocusedInput::None | FocusedInput::Search) => app.select_previous(),
+
+                // Filter the list while typing a search
+                (KeyCode::Char(c), FocusedInput::Search) => {
+                    let search = format!("{}{}", app.list_options.search, c);
+                    app.set_search(search);
+                }
+                (KeyCode::Backspace, FocusedInput::Search) => {
+                    let mut search = app.list_options.search.clone();
+                    search.pop();
+                    app.set_search(search);
+                }
+                (KeyCode::Enter, FocusedInput::Search) => { // Keep the filter and go back to the list
+                    app.focused_input = FocusedInput::None;
+                }
+                (KeyCode::Esc, FocusedInput::Search) => { // Drop the filter
+                    app.set_search(String::new());
+                    app.focused_input = FocusedInput::None;
+                }
 
-                (KeyCode::Tab, _) => { // Cycle the focused field
-                    if active_menu_item == MenuItem::Add {
-                        match focused_input {
-                            FocusedInput::Name => { focused_input = FocusedInput::Category }
-                            FocusedInput::Category => { focused_input = FocusedInput::Text }
-                            FocusedInput::Text => { focused_input = FocusedInput::Name }
-                            FocusedInput::None => { focused_input = FocusedInput::Name }
+                (KeyCode::Tab, _) if app.active_menu_item == MenuItem::Add => { // Cycle the focused field
+                    match app.focused_input {
+                        FocusedInput::Name => { app.focused_input = FocusedInput::Category }
+                        FocusedInput::Category => { app.focused_input = FocusedInput::Tags }
+                        FocusedInput::Tags => { app.focused_input = FocusedInput::Priority }
+                        FocusedInput::Priority => { app.focused_input = FocusedInput::Due }
+                        FocusedInput::Due => { app.focused_input = FocusedInput::Repeat }
+                        FocusedInput::Repeat => { app.focused_input = FocusedInput::BlockedBy }
+                        FocusedInput::BlockedBy => { app.focused_input = FocusedInput::Text }
+                        FocusedInput::Text => { app.focused_input = FocusedInput::Name }
+                        FocusedInput::Search | FocusedInput::Rename | FocusedInput::Checklist
+                        | FocusedInput::ChecklistItem | FocusedInput::None => {
+                            app.focused_input = FocusedInput::Name
                         }
                     }
                 }
@@ -627,37 +4925,564 @@ fn main() {
                 (KeyCode::Backspace, FocusedInput::None) => {}
 
                 // Add character to the corresponding field
-                (KeyCode::Char(c), FocusedInput::Name) => {inputs.name.push(c)}
-                (KeyCode::Char(c), FocusedInput::Category) => {inputs.category.push(c)}
-                (KeyCode::Char(c), FocusedInput::Text) => {inputs.text.push(c)}
+                (KeyCode::Char(c), FocusedInput::Name) => {app.inputs.name.push(c)}
+                (KeyCode::Char(c), FocusedInput::Category) => {app.inputs.category.push(c)}
+                (KeyCode::Char(c), FocusedInput::Tags) => {app.inputs.tags.push(c)}
+                (KeyCode::Char(c), FocusedInput::Text) => {app.inputs.text.push(c)}
+                (KeyCode::Char(c), FocusedInput::Due) => {app.inputs.due.push(c)}
+                (KeyCode::Char(c), FocusedInput::Repeat) => {app.inputs.repeat.push(c)}
+                (KeyCode::Char(c), FocusedInput::BlockedBy) => {app.inputs.blocked_by.push(c)}
+                (KeyCode::Char(c), FocusedInput::Priority) => { // Pick the priority by its number
+                    if let Some(level) = c.to_digit(10).and_then(|d| Priority::ALL.get(d as usize)) {
+                        app.inputs.priority = *level;
+                    }
+                }
+
+                // Change the priority
+                (KeyCode::Right, FocusedInput::Priority) => {app.inputs.priority = app.inputs.priority.raised()}
+                (KeyCode::Left, FocusedInput::Priority) => {app.inputs.priority = app.inputs.priority.lowered()}
 
                 // Remove character from the corresponding field
-                (KeyCode::Backspace, FocusedInput::Name) => {inputs.name.pop();}
-                (KeyCode::Backspace, FocusedInput::Category) => {inputs.category.pop();}
-                (KeyCode::Backspace, FocusedInput::Text) => {inputs.text.pop();}
+                (KeyCode::Backspace, FocusedInput::Name) => {app.inputs.name.pop();}
+                (KeyCode::Backspace, FocusedInput::Category) => {app.inputs.category.pop();}
+                (KeyCode::Backspace, FocusedInput::Tags) => {app.inputs.tags.pop();}
+                (KeyCode::Backspace, FocusedInput::Text) => {app.inputs.text.pop();}
+                (KeyCode::Backspace, FocusedInput::Due) => {app.inputs.due.pop();}
+                (KeyCode::Backspace, FocusedInput::Repeat) => {app.inputs.repeat.pop();}
+                (KeyCode::Backspace, FocusedInput::BlockedBy) => {app.inputs.blocked_by.pop();}
+                (KeyCode::Backspace, FocusedInput::Priority) => {app.inputs.priority = Priority::None}
 
 
+                (KeyCode::Esc, FocusedInput::None) if app.editing.is_some() || app.inputs.parent.is_some() => { // Discard the changes
+                    app.editing = None;
+                    app.inputs = InputStates::default();
+                }
+                (KeyCode::Esc, FocusedInput::None) if !app.list_options.search.is_empty() => {
+                    app.set_search(String::new());
+                }
                 (KeyCode::Esc, FocusedInput::None) => {}
                 (KeyCode::Esc, _) => {  // Clear the focused input so user can switch to another tab
-                    focused_input = FocusedInput::None
+                    app.focused_input = FocusedInput::None
                 }
 
-                (KeyCode::Enter, _) => { // Save new to_do to the db and clean fields
-                    if active_menu_item == MenuItem::Add {
-                        add_todo_from_input_to_db(&inputs).expect("Can add TODO");
-                        focused_input = FocusedInput::None;
-                        inputs = InputStates {
-                            name: "".to_string(),
-                            category: "".to_string(),
-                            text: "".to_string(),
+                (KeyCode::Enter, FocusedInput::None) if app.active_menu_item == MenuItem::Backups => {
+                    let backup = list_backups(db_path())
+                        .ok()
+                        .and_then(|backups| backups.get(app.backup_list_state.selected()?).cloned());
+                    if let Some(backup) = backup { // Restore the selected backup
+                        let message = match restore_backup(&backup) {
+                            Ok(_) => format!("Restored {}", backup.display()),
+                            Err(error) => format!("Could not restore {}: {}", backup.display(), error),
+                        };
+                        app.reload();
+                        app.status_message = Some(message);
+                        app.backup_list_state.select(Some(0));
+                    }
+                }
+
+                (KeyCode::Enter, _) if app.active_menu_item == MenuItem::Add => { // Save the to_do to the db and clean fields
+                    let result = match app.editing {
+                        Some(id) => update_todo_from_input(&mut app.store, id, &app.inputs),
+                        None => add_todo_from_input(&mut app.store, &app.inputs),
+                    };
+                    match result {
+                        Err(Error::ParseDueError(_)) => { // Let the user fix the due date
+                            app.focused_input = FocusedInput::Due;
+                        }
+                        Err(error) => { // Keep the input so nothing typed is lost
+                            app.status_message = Some(error.to_string());
                         }
+                        Ok(change) if app.save(&change) => {
+                            app.record(change);
+                            app.focused_input = FocusedInput::None;
+                            let subtask = app.inputs.parent.is_some();
+                            app.inputs = InputStates::default();
+                            if app.editing.take().is_some() || subtask { // Go back to the edited to_do or the parent
+                                app.active_menu_item = MenuItem::TODOs;
+                            }
+                        }
+                        Ok(_) => {} // Keep the input so nothing typed is lost
                     }
                 }
 
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Tick | Event::Changed => app.refresh(), // Pick up changes made outside of the app
         } // End of input match
     } // End of draw loop
 }
-// ----------------------------------        END OF FN MAIN       ----------------------------------
\ No newline at end of file
+// ----------------------------------        END OF FN MAIN       ----------------------------------
+
+// ----------------------------------            TESTS             ----------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> { // A local time, whatever the zone
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).earliest().expect("is a valid local time")
+    }
+
+    #[test]
+    fn parse_due_understands_phrases_and_dates() {
+        let now = local(2024, 3, 15, 10, 0); // A Friday
+        let due = |input| parse_due(input, now).expect("is a valid due date");
+        let end_of = |year, month, day| Some(local(year, month, day, 23, 59).with_timezone(&Utc) + Duration::seconds(59));
+
+        assert_eq!(due(""), None);
+        assert_eq!(due("  "), None);
+        assert_eq!(due("now"), Some(now.with_timezone(&Utc)));
+        assert_eq!(due("Today"), end_of(2024, 3, 15));
+        assert_eq!(due("tomorrow"), end_of(2024, 3, 16));
+        assert_eq!(due("in 2h"), Some((now + Duration::hours(2)).with_timezone(&Utc)));
+        assert_eq!(due("in 3 days"), Some((now + Duration::days(3)).with_timezone(&Utc)));
+        assert_eq!(due("monday"), end_of(2024, 3, 18));
+        assert_eq!(due("next friday"), end_of(2024, 3, 22)); // Never today
+        assert_eq!(due("2024-04-01"), end_of(2024, 4, 1));
+        assert_eq!(due("2024-04-01 09:30"), Some(local(2024, 4, 1, 9, 30).with_timezone(&Utc)));
+        assert_eq!(due("2024-04-01T09:30"), Some(local(2024, 4, 1, 9, 30).with_timezone(&Utc)));
+        assert_eq!(due("2024-04-01T09:30:00Z"), Some(Utc.with_ymd_and_hms(2024, 4, 1, 9, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn parse_due_rejects_unknown_phrases() {
+        let now = local(2024, 3, 15, 10, 0);
+        for input in ["next blursday", "in 3 fortnights", "in days", "2024-02-30"] {
+            assert!(
+                matches!(parse_due(&format!(" {} ", input), now), Err(Error::ParseDueError(phrase)) if phrase == input),
+                "{} should not parse", input
+            );
+        }
+    }
+
+    #[test]
+    fn format_countdown_rounds_to_the_largest_unit() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 0, 0).unwrap();
+        let countdown = |offset: Duration| format_countdown(now + offset, now);
+
+        assert_eq!(countdown(Duration::seconds(29)), "due in 0m");
+        assert_eq!(countdown(Duration::seconds(90)), "due in 2m");
+        assert_eq!(countdown(Duration::minutes(59)), "due in 59m");
+        assert_eq!(countdown(Duration::minutes(150)), "due in 2h");
+        assert_eq!(countdown(Duration::days(1)), "due in 1 day");
+        assert_eq!(countdown(Duration::days(3) + Duration::hours(5)), "due in 3 days");
+        assert_eq!(countdown(-Duration::minutes(5)), "5m overdue");
+        assert_eq!(countdown(-Duration::days(2)), "2 days overdue");
+    }
+
+    fn todo_with_id(id: usize, name: &str) -> Todo {
+        let mut todo = Todo::default();
+        todo.id = id;
+        todo.name = name.to_owned();
+        todo
+    }
+
+    #[test]
+    fn allocate_id_never_hands_out_a_used_id() {
+        let mut store = Store::default();
+        assert_eq!(store.allocate_id(), 1);
+        assert_eq!(store.allocate_id(), 2);
+
+        let mut store = Store { next_id: 5, todos: vec![todo_with_id(3, "a"), todo_with_id(7, "b")] };
+        assert_eq!(store.allocate_id(), 8); // Past the highest id even if next_id is behind
+        assert_eq!(store.allocate_id(), 9);
+
+        store.todos.retain(|todo| todo.id != 7); // Ids of purged to_dos are not reused
+        assert_eq!(store.allocate_id(), 10);
+
+        let mut store = Store { next_id: 20, todos: vec![todo_with_id(3, "a")] };
+        assert_eq!(store.allocate_id(), 20);
+    }
+
+    #[test]
+    fn repair_ids_gives_duplicates_new_ids() {
+        let mut store = Store {
+            next_id: 0,
+            todos: vec![todo_with_id(1, "a"), todo_with_id(2, "b"), todo_with_id(2, "c"), todo_with_id(5, "d")],
+        };
+        store.todos[3].parent = Some(2);
+        assert_eq!(store.duplicate_ids(), vec![2]);
+
+        assert_eq!(store.repair_ids(false), vec![(2, 6, "c".to_owned())]);
+        assert_eq!(store.todos.iter().map(|todo| todo.id).collect::<Vec<_>>(), vec![1, 2, 6, 5]);
+        assert_eq!(store.todos[3].parent, Some(2)); // Stays with the first to_do that had the id
+        assert!(store.todos.iter().all(|todo| todo.uuid.is_some()));
+        assert!(store.duplicate_ids().is_empty());
+        assert_eq!(store.allocate_id(), 7);
+    }
+
+    #[test]
+    fn duplicate_ids_lists_each_shared_id_once() {
+        let store = Store {
+            next_id: 0,
+            todos: [2, 1, 2, 1, 3, 1].into_iter().map(|id| todo_with_id(id, "")).collect(),
+        };
+        assert_eq!(store.duplicate_ids(), vec![1, 2]);
+    }
+
+    #[test]
+    fn repair_ids_renumbers_and_follows_references() {
+        let mut store = Store {
+            next_id: 13,
+            todos: vec![todo_with_id(4, "a"), todo_with_id(9, "b"), todo_with_id(9, "c")],
+        };
+        store.todos[2].parent = Some(4);
+        store.todos[2].blocked_by = vec![9, 12]; // 12 was purged
+
+        assert_eq!(
+            store.repair_ids(true),
+            vec![(4, 1, "a".to_owned()), (9, 2, "b".to_owned()), (9, 3, "c".to_owned())]
+        );
+        assert_eq!(store.todos.iter().map(|todo| todo.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(store.todos[2].parent, Some(1));
+        assert_eq!(store.todos[2].blocked_by, vec![2]);
+        assert_eq!(store.allocate_id(), 4);
+        assert!(store.repair_ids(true).is_empty()); // Nothing left to change
+    }
+
+    fn snapshot(store: &Store) -> serde_json::Value { // Everything that is saved, to compare stores
+        serde_json::to_value(store).expect("can serialize the store")
+    }
+
+    #[test]
+    fn inverse_changes_undo_and_changes_redo() {
+        let mut store = Store { next_id: 0, todos: vec![todo_with_id(1, "a"), todo_with_id(2, "b")] };
+        let before = snapshot(&store);
+        let change = change_status_with_id(&mut store, 2, TodoStatus::Done).expect("can change the status");
+        let after = snapshot(&store);
+
+        change.inverse().apply(&mut store).expect("can undo");
+        assert_eq!(snapshot(&store), before);
+        change.apply(&mut store).expect("can redo");
+        assert_eq!(snapshot(&store), after);
+        assert_eq!(change.inverse().inverse().describe(), change.describe());
+    }
+
+    #[test]
+    fn undoing_a_purge_puts_the_todo_back_in_place() {
+        let mut store = Store { next_id: 4, todos: vec![todo_with_id(1, "a"), todo_with_id(2, "b"), todo_with_id(3, "c")] };
+        let before = snapshot(&store);
+        let change = remove_todo_with_id(&mut store, 2).expect("can purge");
+        assert_eq!(store.todos.len(), 2);
+
+        change.inverse().apply(&mut store).expect("can undo");
+        assert_eq!(snapshot(&store), before);
+        assert!(matches!(change.inverse().apply(&mut store), Err(Error::DuplicateId(2)))); // Already back
+        change.apply(&mut store).expect("can redo");
+        assert!(matches!(change.apply(&mut store), Err(Error::TodoNotFound(2))));
+    }
+
+    #[test]
+    fn batches_apply_in_order_and_all_or_nothing() {
+        let mut store = Store::default();
+        let added = |id, name| Change::Added { index: usize::MAX, todo: todo_with_id(id, name) };
+        let batch = Change::Batch { description: "adding two".to_owned(), changes: vec![added(1, "a"), added(2, "b")] };
+
+        batch.apply(&mut store).expect("can add both");
+        assert_eq!(store.todos.iter().map(|todo| todo.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(store.allocate_id(), 3);
+
+        let failing = Change::Batch { description: "adding again".to_owned(), changes: vec![added(3, "c"), added(1, "a")] };
+        let before = snapshot(&store);
+        assert!(matches!(failing.apply(&mut store), Err(Error::DuplicateId(1))));
+        assert_eq!(snapshot(&store), before); // 3 was not added either
+
+        batch.inverse().apply(&mut store).expect("can undo"); // Removes 2 before 1
+        assert!(store.todos.is_empty());
+    }
+
+    #[test]
+    fn undoing_a_move_restores_the_positions() {
+        let mut store = Store { next_id: 0, todos: vec![todo_with_id(1, "a"), todo_with_id(2, "b")] };
+        store.todos[1].position = 1;
+        let change = Change::Moved { todo: store.todos[1].clone(), before: vec![(1, 0), (2, 1)], after: vec![(1, 1), (2, 0)] };
+        let positions = |store: &Store| store.todos.iter().map(|todo| todo.position).collect::<Vec<_>>();
+
+        change.apply(&mut store).expect("can move");
+        assert_eq!(positions(&store), vec![1, 0]);
+        change.inverse().apply(&mut store).expect("can undo");
+        assert_eq!(positions(&store), vec![0, 1]);
+    }
+
+    #[test]
+    fn check_blockers_rejects_cycles() {
+        let blocked = |id, blocked_by: Vec<usize>| {
+            let mut todo = todo_with_id(id, "");
+            todo.blocked_by = blocked_by;
+            todo
+        };
+        let todos = vec![
+            blocked(1, vec![]),
+            blocked(2, vec![1]),
+            blocked(3, vec![2]),
+            blocked(4, vec![1, 2, 3]),
+            blocked(5, vec![6]), // A cycle that was written by hand
+            blocked(6, vec![5]),
+            blocked(7, vec![]),
+        ];
+
+        assert!(check_blockers(&todos, 7, &[]).is_ok());
+        assert!(check_blockers(&todos, 7, &[1, 4]).is_ok());
+        assert!(check_blockers(&todos, 7, &[5]).is_ok()); // Does not loop forever
+        assert!(matches!(check_blockers(&todos, 7, &[8]), Err(Error::TodoNotFound(8))));
+        assert!(matches!(check_blockers(&todos, 7, &[7]), Err(Error::DependencyCycle(7, 7))));
+        assert!(matches!(check_blockers(&todos, 1, &[2]), Err(Error::DependencyCycle(1, 2))));
+        assert!(matches!(check_blockers(&todos, 1, &[7, 4]), Err(Error::DependencyCycle(1, 4)))); // Through 2 and 3
+        assert!(matches!(check_blockers(&todos, 2, &[3]), Err(Error::DependencyCycle(2, 3))));
+    }
+
+    #[test]
+    fn parse_recurrence_understands_rules() {
+        let rule = |input| parse_recurrence(input).expect("is a valid rule");
+        let weekly = |weekdays: &[Weekday]| Some(Recurrence::Weekly { weekdays: weekdays.to_vec() });
+
+        assert_eq!(rule(""), None);
+        assert_eq!(rule("daily"), Some(Recurrence::Daily));
+        assert_eq!(rule("Every Day"), Some(Recurrence::Daily));
+        assert_eq!(rule("weekdays"), weekly(&[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]));
+        assert_eq!(rule("weekly on fri, mon"), weekly(&[Weekday::Mon, Weekday::Fri]));
+        assert_eq!(rule("every tuesdays and tuesday"), weekly(&[Weekday::Tue]));
+        assert_eq!(rule("monthly on the 15th"), Some(Recurrence::Monthly { day: 15 }));
+        assert_eq!(rule("every month 31"), Some(Recurrence::Monthly { day: 31 }));
+        assert_eq!(rule("every day after completion"), Some(Recurrence::AfterCompletion { days: 1 }));
+        assert_eq!(rule("every 3 days after done"), Some(Recurrence::AfterCompletion { days: 3 }));
+        assert_eq!(rule("every 10 days"), Some(Recurrence::AfterCompletion { days: 10 }));
+
+        for input in ["biweekly", "weekly", "weekly on someday", "monthly 0", "monthly 32", "every 0 days", "every 3 weeks"] {
+            assert!(
+                matches!(parse_recurrence(input), Err(Error::ParseRecurrenceError(rule)) if rule == input),
+                "{} should not parse", input
+            );
+        }
+    }
+
+    #[test]
+    fn described_rules_parse_back() {
+        for rule in [
+            Recurrence::Daily,
+            Recurrence::Weekly { weekdays: vec![Weekday::Sun] },
+            Recurrence::Weekly { weekdays: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri] },
+            Recurrence::Monthly { day: 1 },
+            Recurrence::Monthly { day: 22 },
+            Recurrence::Monthly { day: 31 },
+            Recurrence::AfterCompletion { days: 1 },
+            Recurrence::AfterCompletion { days: 14 },
+        ] {
+            assert_eq!(parse_recurrence(&rule.describe()).expect("is a valid rule"), Some(rule));
+        }
+    }
+
+    #[test]
+    fn next_due_keeps_the_time_and_skips_missed_occurrences() {
+        let at = |year, month, day, hour| local(year, month, day, hour, 0).with_timezone(&Utc);
+        let next = |rule: Recurrence, due, completed| rule.next_due(due, completed);
+
+        assert_eq!(next(Recurrence::Daily, Some(at(2024, 3, 15, 9)), at(2024, 3, 15, 8)), Some(at(2024, 3, 16, 9)));
+        assert_eq!(next(Recurrence::Daily, Some(at(2024, 3, 10, 9)), at(2024, 3, 15, 8)), Some(at(2024, 3, 16, 9)));
+        assert_eq!(next(Recurrence::Daily, Some(at(2024, 3, 20, 9)), at(2024, 3, 15, 8)), Some(at(2024, 3, 21, 9)));
+        assert_eq!(
+            next(Recurrence::Weekly { weekdays: vec![Weekday::Mon, Weekday::Fri] }, Some(at(2024, 3, 15, 9)), at(2024, 3, 15, 8)),
+            Some(at(2024, 3, 18, 9))
+        );
+        assert_eq!(
+            next(Recurrence::AfterCompletion { days: 3 }, Some(at(2024, 3, 1, 9)), at(2024, 3, 15, 20)),
+            Some(at(2024, 3, 18, 9))
+        );
+        let end_of_day = |year, month, day| Some(local(year, month, day, 23, 59).with_timezone(&Utc) + Duration::seconds(59));
+        assert_eq!(next(Recurrence::Daily, None, at(2024, 3, 15, 8)), end_of_day(2024, 3, 16));
+    }
+
+    #[test]
+    fn next_due_of_monthly_rules_clamps_to_the_end_of_the_month() {
+        let at = |year, month, day| local(year, month, day, 9, 0).with_timezone(&Utc);
+        let monthly = |day, due| Recurrence::Monthly { day }.next_due(Some(due), due);
+
+        assert_eq!(monthly(31, at(2024, 1, 31)), Some(at(2024, 2, 29)));
+        assert_eq!(monthly(31, at(2024, 2, 29)), Some(at(2024, 3, 31)));
+        assert_eq!(monthly(31, at(2023, 1, 31)), Some(at(2023, 2, 28)));
+        assert_eq!(monthly(30, at(2024, 3, 30)), Some(at(2024, 4, 30)));
+        assert_eq!(monthly(15, at(2024, 12, 15)), Some(at(2025, 1, 15)));
+        assert_eq!(monthly(15, at(2024, 3, 1)), Some(at(2024, 3, 15))); // Later this month
+        assert_eq!(
+            Recurrence::Monthly { day: 31 }.next_due(None, at(2024, 4, 10)),
+            Some(local(2024, 4, 30, 23, 59).with_timezone(&Utc) + Duration::seconds(59))
+        );
+    }
+
+    #[test]
+    fn blockers_that_are_done_or_trashed_do_not_block() {
+        let mut todos = vec![todo_with_id(1, "a"), todo_with_id(2, "b"), todo_with_id(3, "c"), todo_with_id(4, "d")];
+        todos[1].set_status(TodoStatus::Done);
+        todos[2].deleted_at = Some(Utc::now());
+        todos[3].blocked_by = vec![2, 3, 9]; // 9 was purged
+        let ids = index_by_id(&todos);
+        assert!(!is_blocked(&todos, &ids, &todos[3]));
+
+        todos[3].blocked_by.push(1);
+        assert!(is_blocked(&todos, &ids, &todos[3]));
+        assert!(Filter::Blocked.matches(&todos[3], &todos, &ids));
+        assert!(!Filter::Ready.matches(&todos[3], &todos, &ids));
+        assert!(Filter::Ready.matches(&todos[0], &todos, &ids));
+        assert!(!Filter::Ready.matches(&todos[1], &todos, &ids)); // Done already
+    }
+
+    fn filter_error_at(query: &str) -> (usize, String) { // Column and message of a query that must not parse
+        match parse_filter(query, local(2024, 3, 15, 10, 0)) {
+            Err(Error::ParseFilterError(column, message)) => (column, message),
+            other => panic!("{} should not parse, got {:?}", query, other),
+        }
+    }
+
+    #[test]
+    fn parse_filter_reads_words_tags_and_conditions() {
+        let filters = parse_filter(
+            r#"category:WORK -status:done "deploy now" #Ops name:~dpl priority:>low tag:#home text:"say \"hi\"" is:ready"#,
+            local(2024, 3, 15, 10, 0),
+        ).expect("is a valid query");
+        assert_eq!(
+            format!("{:?}", filters),
+            concat!(
+                r#"[Category(Exact("WORK")), Not(Status(Done)), Word("deploy now"), Tag(Exact("Ops")), "#,
+                r#"Name(Fuzzy("dpl")), Priority(Greater, Low), Tag(Exact("home")), Text(Contains("say \"hi\"")), Ready]"#,
+            )
+        );
+    }
+
+    #[test]
+    fn parse_condition_turns_dates_into_ranges() {
+        let now = local(2024, 3, 15, 10, 0);
+        let midnight = |year, month, day| Some(local(year, month, day, 0, 0).with_timezone(&Utc));
+        let filter = |query| parse_filter(query, now).expect("is a valid query").remove(0);
+
+        assert!(matches!(filter("due:2024-03-20"), Filter::Date(DateField::Due, from, until)
+            if from == midnight(2024, 3, 20) && until == midnight(2024, 3, 21)));
+        assert!(matches!(filter("created:this-month"), Filter::Date(DateField::Created, from, until)
+            if from == midnight(2024, 3, 1) && until == midnight(2024, 4, 1)));
+        assert!(matches!(filter("completed:last-week"), Filter::Date(DateField::Completed, from, until)
+            if from == midnight(2024, 3, 4) && until == midnight(2024, 3, 11)));
+        assert!(matches!(filter("due:<today"), Filter::Date(DateField::Due, None, until)
+            if until == midnight(2024, 3, 15)));
+        assert!(matches!(filter("updated:>2023-12"), Filter::Date(DateField::Updated, from, None)
+            if from == midnight(2024, 1, 1)));
+        assert!(matches!(filter("due:none"), Filter::NoDate(DateField::Due)));
+    }
+
+    #[test]
+    fn parse_filter_points_at_the_bad_column() {
+        assert_eq!(filter_error_at("status:"), (8, "status: needs a value".to_owned()));
+        assert_eq!(filter_error_at("deploy -owner:me"), (9, "unknown field 'owner'".to_owned()));
+        assert_eq!(filter_error_at("id:x"), (4, "expected a number after id:, not 'x'".to_owned()));
+        assert_eq!(filter_error_at("name:<x"), (6, "name: can not be used with '<'".to_owned()));
+        assert_eq!(filter_error_at("due:someday"),
+                   (5, "expected a date like 2024-01-31, today, last-week or none after due:, not 'someday'".to_owned()));
+        assert_eq!(filter_error_at("a -"), (4, "expected a term after '-'".to_owned()));
+        assert_eq!(filter_error_at(r#"word"x""#), (5, "a quote has to start a word or value".to_owned()));
+        assert_eq!(filter_error_at(r#"ok text:"open"#), (9, "the quote is never closed".to_owned()));
+    }
+
+    #[test]
+    fn parsed_filters_match_todos() {
+        let todo = |id, name: &str, category: &str, status| {
+            let mut todo = Todo::default();
+            todo.id = id;
+            todo.name = name.to_owned();
+            todo.category = category.to_owned();
+            todo.set_status(status);
+            todo
+        };
+        let todos = vec![
+            todo(1, "Deploy the site", "WORK", TodoStatus::Open),
+            todo(2, "Deploy the docs", "WORK", TodoStatus::Done),
+            todo(3, "Water the plants", "HOME", TodoStatus::Open),
+        ];
+        let ids = index_by_id(&todos);
+        let matching = |query| {
+            let filters = parse_filter(query, Local::now()).expect("is a valid query");
+            todos
+                .iter()
+                .filter(|todo| filters.iter().all(|filter| filter.matches(todo, &todos, &ids)))
+                .map(|todo| todo.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching(""), vec![1, 2, 3]);
+        assert_eq!(matching("dpl"), vec![1, 2]);
+        assert_eq!(matching("category:work -status:done"), vec![1]);
+        assert_eq!(matching("name:the -category:WORK"), vec![3]);
+        assert_eq!(matching("id:2"), vec![2]);
+        assert_eq!(matching("completed:none"), vec![1, 3]);
+    }
+
+    fn temp_db(test: &str) -> PathBuf { // Path of a db in a fresh directory of its own
+        let dir = env::temp_dir().join(format!("todo-cli-{}-{}", process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("can create a temporary directory");
+        dir.join(DB_FILE_NAME)
+    }
+
+    #[test]
+    fn read_db_creates_a_missing_db_and_takes_a_blank_one_as_empty() {
+        let path = temp_db("missing");
+        assert!(read_db(&path).expect("can read the db").todos.is_empty());
+        assert_eq!(snapshot(&read_db(&path).expect("can read the db")), snapshot(&Store::default()));
+
+        fs::write(&path, " \n\t").expect("can write");
+        assert!(read_db(&path).expect("can read the db").todos.is_empty());
+        assert_eq!(fs::read(&path).expect("can read"), b" \n\t"); // Reading never writes an existing db
+        fs::remove_dir_all(db_dir(&path)).expect("can clean up");
+    }
+
+    #[test]
+    fn read_db_leaves_a_corrupt_db_alone() {
+        let path = temp_db("corrupt");
+        for contents in [&br#"{"next_id":3,"todos":[{"id":1,"name":"a"#[..], b"\xff\xfe[]", b"null"] {
+            fs::write(&path, contents).expect("can write");
+            assert!(matches!(read_db(&path), Err(Error::ParseDBError(_))));
+            assert_eq!(fs::read(&path).expect("can read"), contents);
+
+            let quarantine = quarantine_db(&path).expect("can keep a copy");
+            assert_eq!(fs::read(&quarantine).expect("can read the copy"), contents);
+        }
+        assert!(list_backups(&path).expect("can list backups").is_empty());
+        fs::remove_dir_all(db_dir(&path)).expect("can clean up");
+    }
+
+    #[test]
+    fn read_db_leaves_an_unreadable_db_alone() {
+        let path = temp_db("unreadable");
+        fs::create_dir(&path).expect("can create a directory where the db should be");
+        assert!(matches!(read_db(&path), Err(Error::ReadDBError(_))));
+        assert!(path.is_dir());
+        fs::remove_dir_all(db_dir(&path)).expect("can clean up");
+    }
+
+    #[test]
+    fn write_db_replaces_the_file_through_a_temporary_one() {
+        let path = temp_db("write");
+        fs::write(db_sibling(&path, ".tmp"), "left over by a crash").expect("can write");
+        let store = Store { next_id: 2, todos: vec![todo_with_id(1, "a")] };
+
+        write_db(&path, &store).expect("can write the db");
+        assert!(!db_sibling(&path, ".tmp").exists());
+        assert_eq!(snapshot(&read_db(&path).expect("can read the db")), snapshot(&store));
+        assert!(list_backups(&path).expect("can list backups").is_empty()); // Nothing to back up yet
+        fs::remove_dir_all(db_dir(&path)).expect("can clean up");
+    }
+
+    #[test]
+    fn write_db_keeps_the_newest_backups() {
+        let path = temp_db("backups");
+        let mut store = Store::default();
+        for id in 1..=backup_count() + 3 {
+            store.todos.push(todo_with_id(id, ""));
+            write_db(&path, &store).expect("can write the db");
+            thread::sleep(std::time::Duration::from_millis(2)); // Backups are named after the millisecond
+        }
+
+        let backups = list_backups(&path).expect("can list backups");
+        assert_eq!(backups.len(), backup_count());
+        let newest = parse_store(&fs::read(&backups[0]).expect("can read the backup")).expect("is a db");
+        assert_eq!(newest.todos.len(), backup_count() + 2); // The version before the last write
+        fs::remove_dir_all(db_dir(&path)).expect("can clean up");
+    }
+}
+// ----------------------------------         END OF TESTS         ----------------------------------